@@ -0,0 +1,420 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+//! A stand-in for the Eclipse Kuksa Databroker.
+//!
+//! The mock serves the subset of the `kuksa.val.v1` gRPC API that is used by the
+//! Horn service and the Kuksa providers of the horn actuator. It records every request
+//! setting target values together with the time it was received, notifies subscribers
+//! of the changed values and can be configured to answer with an additional latency
+//! or with injected errors.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use kuksa_rust_sdk::v1_proto::val_server::{Val, ValServer};
+use kuksa_rust_sdk::v1_proto::{
+    self, DataEntry, DataEntryError, Datapoint, EntryUpdate, Field, GetRequest, GetResponse,
+    GetServerInfoRequest, GetServerInfoResponse, SetRequest, SetResponse, StreamedUpdateRequest,
+    StreamedUpdateResponse, SubscribeEntry, SubscribeRequest, SubscribeResponse,
+};
+use log::{debug, info, warn};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{Code, Request, Response, Status, Streaming};
+
+/// An error the mock answers with instead of applying a `Set` request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// The gRPC call fails with the given status code.
+    Status(Code),
+    /// The call succeeds but every entry is rejected with the given Kuksa error code, e.g. 404.
+    Entry(u32),
+}
+
+impl FromStr for Failure {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(code) = s.parse::<u32>() {
+            return Ok(Failure::Entry(code));
+        }
+        let code = match s.to_lowercase().replace('_', "-").as_str() {
+            "cancelled" => Code::Cancelled,
+            "unknown" => Code::Unknown,
+            "invalid-argument" => Code::InvalidArgument,
+            "deadline-exceeded" => Code::DeadlineExceeded,
+            "not-found" => Code::NotFound,
+            "already-exists" => Code::AlreadyExists,
+            "permission-denied" => Code::PermissionDenied,
+            "resource-exhausted" => Code::ResourceExhausted,
+            "failed-precondition" => Code::FailedPrecondition,
+            "aborted" => Code::Aborted,
+            "out-of-range" => Code::OutOfRange,
+            "unimplemented" => Code::Unimplemented,
+            "internal" => Code::Internal,
+            "unavailable" => Code::Unavailable,
+            "data-loss" => Code::DataLoss,
+            "unauthenticated" => Code::Unauthenticated,
+            _ => {
+                return Err(format!(
+                    "invalid failure '{s}': expected a gRPC status code name or a numeric entry error code"
+                ))
+            }
+        };
+        Ok(Failure::Status(code))
+    }
+}
+
+/// Describes how the mock deviates from a well-behaving Databroker.
+#[derive(Clone, Debug, Default)]
+pub struct Faults {
+    /// Delay applied to every request before it is answered.
+    pub latency: Duration,
+    /// The error to answer `Set` requests with, if any.
+    pub failure: Option<Failure>,
+    /// Only every n-th `Set` request fails. Values of 0 and 1 let every request fail.
+    pub fail_every: u32,
+}
+
+/// A `Set` request for one or more target values as received by the mock.
+#[derive(Clone, Debug)]
+pub struct TargetValueCall {
+    /// The time at which the request was received.
+    pub received_at: SystemTime,
+    /// The requested target values by VSS path.
+    pub targets: HashMap<String, Datapoint>,
+    /// The failure the request was answered with, if any.
+    pub failure: Option<Failure>,
+}
+
+// The number of responses buffered for a subscriber or a streamed update
+const STREAM_BUFFER: usize = 32;
+
+// The fields of the entries changed by an update, by VSS path
+type Changes = Vec<(String, Vec<i32>)>;
+
+struct Subscription {
+    entries: Vec<SubscribeEntry>,
+    tx: mpsc::Sender<Result<SubscribeResponse, Status>>,
+}
+
+impl Subscription {
+    // Sends the subscribed fields of the changed entries, if any.
+    fn notify(&self, entries: &HashMap<String, DataEntry>, changes: &Changes) {
+        let updates: Vec<EntryUpdate> = self
+            .entries
+            .iter()
+            .filter_map(|subscribed| {
+                let (_, changed_fields) =
+                    changes.iter().find(|(path, _)| *path == subscribed.path)?;
+                let stored = entries.get(&subscribed.path)?;
+                let fields: Vec<i32> = subscribed
+                    .fields
+                    .iter()
+                    .copied()
+                    .filter(|field| changed_fields.contains(field))
+                    .collect();
+                if fields.is_empty() {
+                    return None;
+                }
+                let has = |field: Field| fields.contains(&(field as i32));
+                Some(EntryUpdate {
+                    entry: Some(DataEntry {
+                        path: stored.path.clone(),
+                        value: stored.value.clone().filter(|_| has(Field::Value)),
+                        actuator_target: stored
+                            .actuator_target
+                            .clone()
+                            .filter(|_| has(Field::ActuatorTarget)),
+                        ..Default::default()
+                    }),
+                    fields,
+                })
+            })
+            .collect();
+        if !updates.is_empty() && self.tx.try_send(Ok(SubscribeResponse { updates })).is_err() {
+            warn!("Dropping an update for a subscriber which does not keep up");
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    faults: Faults,
+    set_requests: u32,
+    calls: Vec<TargetValueCall>,
+    entries: HashMap<String, DataEntry>,
+    subscriptions: Vec<Subscription>,
+}
+
+/// The mock Databroker. Clones share the recorded calls and the configured faults.
+#[derive(Clone, Default)]
+pub struct MockDatabroker {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockDatabroker {
+    pub fn new(faults: Faults) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                faults,
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Replaces the faults applied to subsequent requests.
+    pub fn set_faults(&self, faults: Faults) {
+        let mut state = self.lock();
+        state.faults = faults;
+        state.set_requests = 0;
+    }
+
+    /// Returns all requests for target values received so far.
+    pub fn target_value_calls(&self) -> Vec<TargetValueCall> {
+        self.lock().calls.clone()
+    }
+
+    /// Forgets the recorded calls and the stored entries.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.calls.clear();
+        state.entries.clear();
+    }
+
+    /// Serves the `kuksa.val.v1` API on the given address until the server fails.
+    pub async fn serve(self, address: SocketAddr) -> Result<(), tonic::transport::Error> {
+        info!("Serving kuksa.val.v1 on [{address}]");
+        tonic::transport::Server::builder()
+            .add_service(ValServer::new(self))
+            .serve(address)
+            .await
+    }
+
+    /// Serves the `kuksa.val.v1` API on a bound listener, e.g. on an ephemeral port in tests.
+    pub async fn serve_with_listener(
+        self,
+        listener: TcpListener,
+    ) -> Result<(), tonic::transport::Error> {
+        if let Ok(address) = listener.local_addr() {
+            info!("Serving kuksa.val.v1 on [{address}]");
+        }
+        tonic::transport::Server::builder()
+            .add_service(ValServer::new(self))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // the state stays consistent even if a holder of the lock panicked
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn delay(&self) {
+        let latency = self.lock().faults.latency;
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+    }
+
+    // Applies the updates of a Set or StreamedUpdate request and returns the errors of the entries.
+    async fn update(&self, updates: Vec<EntryUpdate>) -> Result<Vec<DataEntryError>, Status> {
+        let received_at = SystemTime::now();
+        self.delay().await;
+        let failure = self.next_failure();
+
+        let targets: HashMap<String, Datapoint> = updates
+            .iter()
+            .filter(|update| update.fields.contains(&(Field::ActuatorTarget as i32)))
+            .filter_map(|update| update.entry.as_ref())
+            .filter_map(|entry| {
+                entry
+                    .actuator_target
+                    .clone()
+                    .map(|target| (entry.path.clone(), target))
+            })
+            .collect();
+        if !targets.is_empty() {
+            info!("Received target values {targets:?} (injected failure: {failure:?})");
+            self.lock().calls.push(TargetValueCall {
+                received_at,
+                targets,
+                failure,
+            });
+        }
+
+        match failure {
+            Some(Failure::Status(code)) => Err(Status::new(code, "injected failure")),
+            Some(Failure::Entry(code)) => Ok(updates
+                .into_iter()
+                .filter_map(|update| update.entry)
+                .map(|entry| entry_error(entry.path, code, "injected failure"))
+                .collect()),
+            None => {
+                let mut state = self.lock();
+                let mut changes = Changes::new();
+                for update in updates {
+                    let Some(entry) = update.entry else {
+                        continue;
+                    };
+                    debug!("Applying update for {}", entry.path);
+                    let stored =
+                        state
+                            .entries
+                            .entry(entry.path.clone())
+                            .or_insert_with(|| DataEntry {
+                                path: entry.path.clone(),
+                                ..Default::default()
+                            });
+                    if update.fields.contains(&(Field::Value as i32)) {
+                        stored.value = entry.value;
+                    }
+                    if update.fields.contains(&(Field::ActuatorTarget as i32)) {
+                        stored.actuator_target = entry.actuator_target;
+                    }
+                    changes.push((entry.path, update.fields));
+                }
+                state
+                    .subscriptions
+                    .retain(|subscription| !subscription.tx.is_closed());
+                for subscription in &state.subscriptions {
+                    subscription.notify(&state.entries, &changes);
+                }
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    fn next_failure(&self) -> Option<Failure> {
+        let mut state = self.lock();
+        state.set_requests += 1;
+        let fail_every = state.faults.fail_every.max(1);
+        state
+            .faults
+            .failure
+            .filter(|_| state.set_requests.is_multiple_of(fail_every))
+    }
+}
+
+fn entry_error(path: String, code: u32, message: &str) -> DataEntryError {
+    DataEntryError {
+        path,
+        error: Some(v1_proto::Error {
+            code,
+            reason: "mock_error".to_string(),
+            message: message.to_string(),
+        }),
+    }
+}
+
+#[tonic::async_trait]
+impl Val for MockDatabroker {
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
+        self.delay().await;
+        let state = self.lock();
+        let mut response = GetResponse::default();
+        for entry in request.into_inner().entries {
+            match state.entries.get(&entry.path) {
+                Some(data_entry) => response.entries.push(data_entry.clone()),
+                None => {
+                    response
+                        .errors
+                        .push(entry_error(entry.path, 404, "no value has been set yet"))
+                }
+            }
+        }
+        Ok(Response::new(response))
+    }
+
+    async fn set(&self, request: Request<SetRequest>) -> Result<Response<SetResponse>, Status> {
+        let errors = self.update(request.into_inner().updates).await?;
+        Ok(Response::new(SetResponse {
+            error: None,
+            errors,
+        }))
+    }
+
+    type StreamedUpdateStream = ReceiverStream<Result<StreamedUpdateResponse, Status>>;
+
+    // Applies the updates of the request stream one by one, answering only with errors
+    async fn streamed_update(
+        &self,
+        request: Request<Streaming<StreamedUpdateRequest>>,
+    ) -> Result<Response<Self::StreamedUpdateStream>, Status> {
+        let mut requests = request.into_inner();
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        let databroker = self.clone();
+        tokio::spawn(async move {
+            while let Ok(Some(request)) = requests.message().await {
+                let response = match databroker.update(request.updates).await {
+                    Ok(errors) if errors.is_empty() => continue,
+                    Ok(errors) => Ok(StreamedUpdateResponse {
+                        error: None,
+                        errors,
+                    }),
+                    Err(status) => Err(status),
+                };
+                if tx.send(response).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    type SubscribeStream = ReceiverStream<Result<SubscribeResponse, Status>>;
+
+    // Like the Databroker, starts with the current values of the subscribed entries
+    // and sends the subscribed fields whenever an update changes them.
+    async fn subscribe(
+        &self,
+        request: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        self.delay().await;
+        let entries = request.into_inner().entries;
+        if entries.is_empty() {
+            return Err(Status::invalid_argument("no entries to subscribe to"));
+        }
+        info!(
+            "Subscribing to {:?}",
+            entries.iter().map(|entry| &entry.path).collect::<Vec<_>>()
+        );
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        let subscription = Subscription { entries, tx };
+        let current: Changes = subscription
+            .entries
+            .iter()
+            .map(|entry| (entry.path.clone(), entry.fields.clone()))
+            .collect();
+        let mut state = self.lock();
+        subscription.notify(&state.entries, &current);
+        state.subscriptions.push(subscription);
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn get_server_info(
+        &self,
+        _request: Request<GetServerInfoRequest>,
+    ) -> Result<Response<GetServerInfoResponse>, Status> {
+        self.delay().await;
+        Ok(Response::new(GetServerInfoResponse {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }))
+    }
+}
@@ -0,0 +1,70 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use clap::Parser;
+use databroker_mock::{Failure, Faults, MockDatabroker};
+use env_logger::Env;
+use log::info;
+
+#[derive(clap::Parser, Clone, Debug)]
+pub struct Args {
+    #[arg(
+        long,
+        default_value = "127.0.0.1:55556",
+        env = "MOCK_DATABROKER_ADDRESS",
+        value_name = "ADDRESS"
+    )]
+    /// The socket address to serve the kuksa.val.v1 API on.
+    address: SocketAddr,
+
+    #[arg(
+        long,
+        default_value = "0",
+        env = "MOCK_DATABROKER_LATENCY",
+        value_name = "MILLISECONDS"
+    )]
+    /// Delay applied to every request before it is answered.
+    latency: u64,
+
+    #[arg(long, env = "MOCK_DATABROKER_FAIL_WITH", value_name = "FAILURE")]
+    /// Answer Set requests with an error instead of applying them.
+    /// Either a gRPC status code name like `unavailable` or a numeric Kuksa error code like `404`.
+    fail_with: Option<Failure>,
+
+    #[arg(
+        long,
+        default_value = "1",
+        env = "MOCK_DATABROKER_FAIL_EVERY",
+        value_name = "N"
+    )]
+    /// Only every n-th Set request is answered with the configured failure.
+    fail_every: u32,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    let args = Args::parse();
+    info!("Starting the mock Kuksa Databroker");
+
+    let faults = Faults {
+        latency: Duration::from_millis(args.latency),
+        failure: args.fail_with,
+        fail_every: args.fail_every,
+    };
+    MockDatabroker::new(faults).serve(args.address).await?;
+    Ok(())
+}
@@ -0,0 +1,166 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::time::Duration;
+
+use databroker_mock::{Failure, Faults, MockDatabroker};
+use kuksa_rust_sdk::v1_proto::val_client::ValClient;
+use kuksa_rust_sdk::v1_proto::{
+    datapoint::Value, DataEntry, Datapoint, EntryRequest, EntryUpdate, Field, GetRequest,
+    SetRequest, SubscribeEntry, SubscribeRequest, SubscribeResponse,
+};
+use tokio::net::TcpListener;
+use tonic::transport::Channel;
+use tonic::{Code, Streaming};
+
+const HORN: &str = "Vehicle.Body.Horn.IsActive";
+
+async fn start(faults: Faults) -> (MockDatabroker, ValClient<Channel>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let databroker = MockDatabroker::new(faults);
+    tokio::spawn(databroker.clone().serve_with_listener(listener));
+    let client = ValClient::connect(format!("http://{address}"))
+        .await
+        .unwrap();
+    (databroker, client)
+}
+
+fn set_target(is_active: bool) -> SetRequest {
+    SetRequest {
+        updates: vec![EntryUpdate {
+            entry: Some(DataEntry {
+                path: HORN.to_string(),
+                actuator_target: Some(Datapoint {
+                    timestamp: None,
+                    value: Some(Value::Bool(is_active)),
+                }),
+                ..Default::default()
+            }),
+            fields: vec![Field::ActuatorTarget as i32],
+        }],
+    }
+}
+
+fn target_of(entry: Option<&DataEntry>) -> Option<Value> {
+    entry?.actuator_target.as_ref()?.value.clone()
+}
+
+async fn next_target(updates: &mut Streaming<SubscribeResponse>) -> Option<Value> {
+    let response = tokio::time::timeout(Duration::from_secs(1), updates.message())
+        .await
+        .expect("no update within 1 s")
+        .unwrap()
+        .expect("the subscription ended");
+    target_of(
+        response
+            .updates
+            .first()
+            .and_then(|update| update.entry.as_ref()),
+    )
+}
+
+#[tokio::test]
+async fn set_target_value_is_recorded_and_stored() {
+    let (databroker, mut client) = start(Faults::default()).await;
+
+    let response = client.set(set_target(true)).await.unwrap().into_inner();
+    assert!(response.errors.is_empty());
+
+    let calls = databroker.target_value_calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].failure, None);
+    assert_eq!(
+        calls[0].targets[HORN].value,
+        Some(Value::Bool(true)),
+        "recorded target value"
+    );
+    let response = client
+        .get(GetRequest {
+            entries: vec![EntryRequest {
+                path: HORN.to_string(),
+                fields: vec![Field::ActuatorTarget as i32],
+                ..Default::default()
+            }],
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(target_of(response.entries.first()), Some(Value::Bool(true)));
+}
+
+#[tokio::test]
+async fn subscriber_receives_target_values() {
+    let (_databroker, mut client) = start(Faults::default()).await;
+    client.set(set_target(false)).await.unwrap();
+
+    let mut updates = client
+        .subscribe(SubscribeRequest {
+            entries: vec![SubscribeEntry {
+                path: HORN.to_string(),
+                fields: vec![Field::ActuatorTarget as i32],
+                ..Default::default()
+            }],
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(
+        next_target(&mut updates).await,
+        Some(Value::Bool(false)),
+        "current value"
+    );
+
+    client.set(set_target(true)).await.unwrap();
+    assert_eq!(
+        next_target(&mut updates).await,
+        Some(Value::Bool(true)),
+        "changed value"
+    );
+}
+
+#[tokio::test]
+async fn injected_failures_are_returned_and_recorded() {
+    let (databroker, mut client) = start(Faults {
+        failure: Some(Failure::Status(Code::Unavailable)),
+        fail_every: 2,
+        ..Default::default()
+    })
+    .await;
+
+    assert!(client.set(set_target(true)).await.is_ok());
+    let status = client.set(set_target(false)).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+
+    databroker.set_faults(Faults {
+        failure: Some(Failure::Entry(404)),
+        ..Default::default()
+    });
+    let response = client.set(set_target(true)).await.unwrap().into_inner();
+    assert_eq!(response.errors.len(), 1);
+    assert_eq!(response.errors[0].error.as_ref().map(|e| e.code), Some(404));
+
+    let failures: Vec<_> = databroker
+        .target_value_calls()
+        .iter()
+        .map(|call| call.failure)
+        .collect();
+    assert_eq!(
+        failures,
+        [
+            None,
+            Some(Failure::Status(Code::Unavailable)),
+            Some(Failure::Entry(404))
+        ]
+    );
+}
@@ -0,0 +1,323 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hdrhistogram::Histogram;
+use horn_proto::pattern::{RequestBuilder, SequenceBuilder};
+use log::{info, warn};
+use serde_json::json;
+use tokio::time::{Instant, MissedTickBehavior};
+use up_rust::communication::InMemoryRpcClient;
+use up_transport_zenoh::UPTransportZenoh;
+
+use crate::config::{Args, BenchArgs};
+use crate::output::{self, OutputFormat};
+use crate::patterns::PatternLibrary;
+use crate::proxy::{HornServiceProxy, Invocation};
+
+// The entity id of the first benchmark client. Each client uses its own
+// instance id in the upper 16 bits, so that responses reach the right client.
+const BENCH_CLIENT_ENTITY_ID: u32 = 0x0001;
+// Latencies are recorded in microseconds with 3 significant digits
+const MAX_RECORDED_LATENCY_US: u64 = 3_600_000_000;
+const SIGNIFICANT_DIGITS: u8 = 3;
+// The code under which failures of the client itself are counted
+const CLIENT_ERROR: &str = "CLIENT_ERROR";
+
+/// The response codes and latencies observed for one method of the Horn service.
+struct MethodStats {
+    codes: BTreeMap<String, u64>,
+    latencies: Histogram<u64>,
+}
+
+impl MethodStats {
+    fn new() -> Self {
+        Self {
+            codes: BTreeMap::new(),
+            latencies: Histogram::new_with_bounds(1, MAX_RECORDED_LATENCY_US, SIGNIFICANT_DIGITS)
+                .expect("valid histogram bounds"),
+        }
+    }
+
+    fn record(&mut self, result: Result<Invocation, Box<dyn std::error::Error>>) {
+        let code = match result {
+            Ok(invocation) => {
+                self.latencies
+                    .saturating_record(invocation.latency.as_micros() as u64);
+                format!("{:?}", invocation.outcome.code())
+            }
+            Err(e) => {
+                warn!("Failed to invoke the Horn service: {e}");
+                CLIENT_ERROR.to_string()
+            }
+        };
+        *self.codes.entry(code).or_default() += 1;
+    }
+
+    fn merge(&mut self, other: &MethodStats) {
+        for (code, count) in &other.codes {
+            *self.codes.entry(code.clone()).or_default() += count;
+        }
+        self.latencies
+            .add(&other.latencies)
+            .expect("histograms with equal bounds");
+    }
+
+    fn requests(&self) -> u64 {
+        self.codes.values().sum()
+    }
+
+    fn errors(&self) -> u64 {
+        self.requests() - self.codes.get("OK").copied().unwrap_or(0)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "requests": self.requests(),
+            "errors": self.errors(),
+            "codes": self.codes,
+            "latencyMs": {
+                "p50": percentile_millis(&self.latencies, 0.5),
+                "p95": percentile_millis(&self.latencies, 0.95),
+                "p99": percentile_millis(&self.latencies, 0.99),
+                "max": self.latencies.max() as f64 / 1000.0,
+                "mean": self.latencies.mean() / 1000.0,
+            },
+        })
+    }
+}
+
+// The interval in which each client sends a request to reach the target rate over all
+// clients. The interval must not round to zero, since the tickers of the clients need a
+// positive period.
+fn request_period(clients: u32, rate: f64) -> Result<Duration, String> {
+    if !rate.is_finite() || rate <= 0.0 {
+        return Err(format!("invalid rate {rate}: must be positive and finite"));
+    }
+    match Duration::try_from_secs_f64(f64::from(clients) / rate) {
+        Ok(period) if !period.is_zero() => Ok(period),
+        Ok(_) => Err(format!(
+            "invalid rate {rate}: too high for {clients} clients"
+        )),
+        Err(_) => Err(format!(
+            "invalid rate {rate}: too low for {clients} clients"
+        )),
+    }
+}
+
+fn percentile_millis(latencies: &Histogram<u64>, quantile: f64) -> f64 {
+    latencies.value_at_quantile(quantile) as f64 / 1000.0
+}
+
+/// The statistics of all clients of a benchmark run.
+pub(crate) struct BenchReport {
+    clients: u32,
+    target_rate: f64,
+    elapsed: Duration,
+    activate: MethodStats,
+    deactivate: MethodStats,
+}
+
+impl BenchReport {
+    fn total(&self) -> MethodStats {
+        let mut total = MethodStats::new();
+        total.merge(&self.activate);
+        total.merge(&self.deactivate);
+        total
+    }
+}
+
+/// Runs the benchmark: every client invokes the Horn service in turn at its share
+/// of the target rate, waiting for each response before sending its next request.
+/// The horn is deactivated at the end of the run.
+pub(crate) async fn run(
+    args: &Args,
+    bench_args: &BenchArgs,
+) -> Result<BenchReport, Box<dyn std::error::Error>> {
+    let clients = bench_args.clients;
+    let period = request_period(clients, bench_args.rate)?;
+    let activate_request = match &bench_args.pattern {
+        Some(name) => PatternLibrary::load(args.patterns.as_deref())?
+            .get(name)?
+            .request
+            .clone(),
+        None => RequestBuilder::sequenced()
+            .sequence(SequenceBuilder::new().cycle(100, 100))
+            .build(),
+    };
+
+    let mut proxies = Vec::with_capacity(bench_args.clients as usize);
+    for index in 0..bench_args.clients {
+        let uri = format!(
+            "//horn_client/{:X}/1/0",
+            ((index + 1) << 16) | BENCH_CLIENT_ENTITY_ID
+        );
+        let transport = UPTransportZenoh::new(args.get_zenoh_config()?, uri.as_str())
+            .await
+            .map(Arc::new)?;
+        let rpc_client = InMemoryRpcClient::new(transport.clone(), transport).await?;
+        proxies.push(HornServiceProxy::new(
+            rpc_client,
+            &args.service,
+            args.timeout,
+        )?);
+    }
+
+    let (activate_weight, deactivate_weight) = bench_args.mix;
+    info!(
+        "Running {clients} clients at {} requests per second for {} s",
+        bench_args.rate, bench_args.duration
+    );
+
+    let start = Instant::now();
+    let end = start + Duration::from_secs(bench_args.duration);
+    let mut tasks = Vec::with_capacity(proxies.len());
+    for (index, proxy) in proxies.into_iter().enumerate() {
+        let activate_request = activate_request.clone();
+        tasks.push(tokio::spawn(async move {
+            let mut activate = MethodStats::new();
+            let mut deactivate = MethodStats::new();
+            // Spread the requests of the clients evenly over the period
+            let mut ticker =
+                tokio::time::interval_at(start + period * index as u32 / clients, period);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            let mut sequence_number = index as u64;
+            loop {
+                if ticker.tick().await >= end {
+                    break;
+                }
+                // Interleave the methods of all clients according to the mix
+                let slot = sequence_number % u64::from(activate_weight + deactivate_weight);
+                if slot < u64::from(activate_weight) {
+                    activate.record(proxy.activate(activate_request.clone()).await);
+                } else {
+                    deactivate.record(proxy.deactivate().await);
+                }
+                sequence_number += u64::from(clients);
+            }
+            (proxy, activate, deactivate)
+        }));
+    }
+
+    let mut report = BenchReport {
+        clients,
+        target_rate: bench_args.rate,
+        elapsed: Duration::ZERO,
+        activate: MethodStats::new(),
+        deactivate: MethodStats::new(),
+    };
+    let mut last_proxy = None;
+    for task in tasks {
+        let (proxy, activate, deactivate) = task.await?;
+        report.activate.merge(&activate);
+        report.deactivate.merge(&deactivate);
+        last_proxy = Some(proxy);
+    }
+    report.elapsed = start.elapsed();
+
+    if let Some(proxy) = last_proxy {
+        if let Err(e) = proxy.deactivate().await {
+            warn!("Failed to deactivate the horn after the benchmark: {e}");
+        }
+    }
+    Ok(report)
+}
+
+/// Prints the throughput, the response codes and the latency percentiles per method.
+pub(crate) fn report(report: &BenchReport, format: OutputFormat) {
+    let total = report.total();
+    let throughput = total.requests() as f64 / report.elapsed.as_secs_f64();
+    if format == OutputFormat::Json {
+        let json = json!({
+            "clients": report.clients,
+            "targetRate": report.target_rate,
+            "elapsedMs": output::millis(report.elapsed),
+            "throughput": throughput,
+            "total": total.to_json(),
+            "activate": report.activate.to_json(),
+            "deactivate": report.deactivate.to_json(),
+        });
+        println!("{json}");
+        return;
+    }
+
+    println!(
+        "{} requests from {} clients in {:.1} s: {throughput:.1} requests per second (target {})",
+        total.requests(),
+        report.clients,
+        report.elapsed.as_secs_f64(),
+        report.target_rate
+    );
+    println!(
+        "{:<14} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}",
+        "method", "requests", "errors", "p50 ms", "p95 ms", "p99 ms", "max ms"
+    );
+    for (method, stats) in [
+        ("ActivateHorn", &report.activate),
+        ("DeactivateHorn", &report.deactivate),
+        ("total", &total),
+    ] {
+        println!(
+            "{method:<14} {:>8} {:>8} {:>10.2} {:>10.2} {:>10.2} {:>10.2}",
+            stats.requests(),
+            stats.errors(),
+            percentile_millis(&stats.latencies, 0.5),
+            percentile_millis(&stats.latencies, 0.95),
+            percentile_millis(&stats.latencies, 0.99),
+            stats.latencies.max() as f64 / 1000.0
+        );
+    }
+    if total.errors() > 0 {
+        println!("errors by code:");
+        for (code, count) in total.codes.iter().filter(|(code, _)| *code != "OK") {
+            println!("  {code}: {count}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_period_spreads_the_rate_over_the_clients() {
+        assert_eq!(request_period(4, 20.0), Ok(Duration::from_millis(200)));
+        assert_eq!(request_period(1, 0.5), Ok(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn request_period_rejects_invalid_rates() {
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(request_period(4, rate).is_err(), "rate {rate}");
+        }
+        // a period below a nanosecond
+        assert!(request_period(1, 1e10).is_err());
+        assert!(request_period(1, 1e-300).is_err());
+    }
+
+    #[test]
+    fn percentiles_are_reported_in_milliseconds() {
+        let mut latencies = MethodStats::new().latencies;
+        for millis in 1..=100 {
+            latencies.saturating_record(millis * 1000);
+        }
+        // the histogram keeps three significant digits
+        let close = |actual: f64, expected: f64| (actual - expected).abs() < expected / 500.0;
+        assert!(close(percentile_millis(&latencies, 0.5), 50.0));
+        assert!(close(percentile_millis(&latencies, 0.99), 99.0));
+        assert!(close(percentile_millis(&latencies, 1.0), 100.0));
+    }
+}
@@ -0,0 +1,220 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::path::PathBuf;
+
+use horn_proto::combination::CombinationMode;
+use horn_proto::horn_topics::HornSequence;
+use up_transport_zenoh::zenoh_config;
+
+use crate::output::OutputFormat;
+
+#[derive(clap::Parser, Clone, Debug)]
+pub struct Args {
+    #[arg(
+        short,
+        long,
+        default_value = "zenoh-config.json5",
+        env = "ZENOH_CONFIG"
+    )]
+    /// A Zenoh configuration file.
+    config: PathBuf,
+
+    #[arg(
+        long,
+        default_value = "horn-service-kuksa",
+        env = "HORN_SERVICE_AUTHORITY",
+        value_name = "AUTHORITY"
+    )]
+    /// The uProtocol authority of the Horn service to invoke.
+    pub service: String,
+
+    #[arg(
+        long,
+        default_value = "1000",
+        env = "HORN_TIMEOUT",
+        value_name = "MILLISECONDS"
+    )]
+    /// The time to wait for a response or status update from the Horn service.
+    pub timeout: u32,
+
+    #[arg(long, env = "HORN_PATTERNS", value_name = "DIRECTORY")]
+    /// A directory with horn pattern files in JSON or YAML format.
+    /// The file name without extension is the name of the pattern.
+    pub patterns: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        env = "HORN_OUTPUT",
+        value_name = "FORMAT"
+    )]
+    /// The format of the results printed by the client.
+    pub output: OutputFormat,
+
+    #[command(subcommand)]
+    /// The action to perform. Runs the demo sequence if not set.
+    pub command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Clone, Debug)]
+pub enum Command {
+    /// Activates the horn, either continuously or with one or more sequences.
+    Activate {
+        #[command(flatten)]
+        request: ActivateArgs,
+
+        #[arg(long, value_name = "MODE")]
+        /// How the request is combined with a request the service is still playing:
+        /// `preempt`, `queue` or `ignore-while-busy`. Uses the mode of the service if not set.
+        combination: Option<CombinationMode>,
+    },
+    /// Deactivates the horn.
+    Deactivate,
+    /// Waits for the next status published by the Horn service and prints it.
+    /// The service repeats its status periodically, so it arrives within the timeout.
+    Status,
+    /// Prints the changes of the status published by the Horn service until interrupted.
+    /// Warns whenever no status arrives within the timeout.
+    Watch {
+        #[arg(long)]
+        /// Prints every received status, including repetitions of the previous status.
+        all: bool,
+    },
+    /// Plays a fixed demo of a sequenced and a continuous activation.
+    Demo,
+    /// Invokes the Horn service from several concurrent clients at a target rate
+    /// and reports throughput, response codes and latency percentiles.
+    Bench(BenchArgs),
+    /// Runs the timed steps of a scenario file and reports whether each step
+    /// returned the expected response code. Exits with an error if a step failed.
+    Scenario {
+        /// A scenario file in YAML or JSON format.
+        file: PathBuf,
+    },
+    /// Lists or describes the available horn patterns.
+    Patterns {
+        #[command(subcommand)]
+        command: PatternsCommand,
+    },
+}
+
+#[derive(clap::Subcommand, Clone, Debug)]
+pub enum PatternsCommand {
+    /// Lists the names of the available horn patterns.
+    List,
+    /// Describes the sequences of a horn pattern.
+    Describe {
+        /// The name of the horn pattern.
+        name: String,
+    },
+}
+
+#[derive(clap::Args, Clone, Debug)]
+#[group(required = true, multiple = false)]
+pub struct ActivateArgs {
+    #[arg(long)]
+    /// Activates the horn until it is deactivated.
+    pub continuous: bool,
+
+    #[arg(long, value_parser = parse_horn_sequence, value_name = "ON/OFF ...")]
+    /// A sequence of horn cycles given as on and off times in milliseconds, e.g. `100/100,200/300`,
+    /// with repetitions like `3x(100/100) 500/30`. Repeat the option to request multiple sequences.
+    pub sequence: Vec<HornSequence>,
+
+    #[arg(long, value_name = "NAME")]
+    /// The name of a built-in horn pattern or of a pattern in the patterns directory.
+    pub pattern: Option<String>,
+
+    #[arg(long, value_name = "PATH")]
+    /// A file containing an ActivateHornRequest in the protobuf JSON mapping, as JSON or YAML.
+    pub file: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Clone, Debug)]
+pub struct BenchArgs {
+    #[arg(long, default_value = "4", value_parser = clap::value_parser!(u32).range(1..))]
+    /// The number of concurrent clients, each with its own transport and RPC client.
+    pub clients: u32,
+
+    #[arg(long, default_value = "20", value_name = "REQUESTS_PER_SECOND")]
+    /// The target rate of requests over all clients.
+    pub rate: f64,
+
+    #[arg(long, default_value = "10", value_name = "SECONDS")]
+    /// How long to issue requests.
+    pub duration: u64,
+
+    #[arg(long, default_value = "1:1", value_parser = parse_mix, value_name = "ACTIVATE:DEACTIVATE")]
+    /// The ratio of ActivateHorn to DeactivateHorn requests, e.g. `3:1`.
+    pub mix: (u32, u32),
+
+    #[arg(long, value_name = "NAME")]
+    /// The horn pattern to activate. Defaults to a single short horn cycle.
+    pub pattern: Option<String>,
+}
+
+fn parse_mix(mix: &str) -> Result<(u32, u32), String> {
+    let (activate, deactivate) = mix
+        .split_once(':')
+        .ok_or_else(|| format!("invalid mix '{mix}': expected ACTIVATE:DEACTIVATE"))?;
+    let parse_weight = |weight: &str| {
+        weight
+            .trim()
+            .parse::<u32>()
+            .map_err(|e| format!("invalid weight '{weight}' in mix '{mix}': {e}"))
+    };
+    let weights = (parse_weight(activate)?, parse_weight(deactivate)?);
+    match weights.0.checked_add(weights.1) {
+        Some(0) => Err(format!(
+            "invalid mix '{mix}': at least one weight must be positive"
+        )),
+        Some(_) => Ok(weights),
+        None => Err(format!(
+            "invalid mix '{mix}': the weights must add up to at most {}",
+            u32::MAX
+        )),
+    }
+}
+
+pub(crate) fn parse_horn_sequence(sequence: &str) -> Result<HornSequence, String> {
+    horn_proto::pattern::parse_sequence(sequence)
+        .map_err(|e| format!("invalid horn sequence '{sequence}': {e}"))
+}
+
+impl Args {
+    pub fn get_zenoh_config(&self) -> Result<zenoh_config::Config, Box<dyn std::error::Error>> {
+        // Load the config from file path
+        zenoh_config::Config::from_file(&self.config).map_err(|e| e as Box<dyn std::error::Error>)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mixes_are_parsed_into_weights() {
+        assert_eq!(parse_mix("3:1"), Ok((3, 1)));
+        assert_eq!(parse_mix(" 0 : 2 "), Ok((0, 2)));
+        assert_eq!(parse_mix("4294967294:1"), Ok((u32::MAX - 1, 1)));
+    }
+
+    #[test]
+    fn invalid_mixes_are_rejected() {
+        for mix in ["3", "a:1", "1:-1", "0:0", "4294967295:1", "4294967296:0"] {
+            assert!(parse_mix(mix).is_err(), "mix {mix}");
+        }
+    }
+}
@@ -15,179 +15,191 @@ use clap::Parser;
 use env_logger::Env;
 use log::error;
 use log::info;
-use std::path::PathBuf;
+use std::path::Path;
+use std::process::ExitCode;
 use std::sync::Arc;
-use up_rust::communication::{CallOptions, InMemoryRpcClient, RpcClient, UPayload};
-use up_transport_zenoh::zenoh_config;
+use std::time::Duration;
+use up_rust::communication::InMemoryRpcClient;
+use up_rust::UCode;
 use up_transport_zenoh::UPTransportZenoh;
 
-use horn_proto::horn_service::{ActivateHornRequest, ActivateHornResponse, DeactivateHornRequest};
-use horn_proto::horn_topics::{HornCycle, HornMode, HornSequence};
+use horn_proto::descriptors::horn;
+use horn_proto::horn_service::ActivateHornRequest;
+use horn_proto::pattern::{RequestBuilder, SequenceBuilder};
 
-const HORN_SERVICE_AUTHORITY_NAME: &str = "horn-service-kuksa";
-// see https://github.com/COVESA/uservices/blob/main/src/main/proto/vehicle/body/horn/v1/horn_service.proto
-const HORN_SERVICE_ENTITY_ID: u32 = 28;
-const ACTIVATE_HORN_RESOURCE_ID: u16 = 0x0001;
-const DEACTIVATE_HORN_RESOURCE_ID: u16 = 0x0002;
+use config::{ActivateArgs, Command, PatternsCommand};
+use output::OutputFormat;
+use patterns::PatternLibrary;
+use proxy::HornServiceProxy;
+use scenario::Scenario;
+
+mod bench;
+mod config;
+mod output;
+mod patterns;
+mod proxy;
+mod scenario;
+mod status;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
-    let args = Args::parse();
+    let args = config::Args::parse();
+
+    if let Some(Command::Patterns { command }) = &args.command {
+        let library = PatternLibrary::load(args.patterns.as_deref())?;
+        match command {
+            PatternsCommand::List => patterns::list(&library, args.output),
+            PatternsCommand::Describe { name } => {
+                patterns::describe(name, library.get(name)?, args.output)?
+            }
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
 
     info!("Starting the client for the COVESA Horn service over uProtocol");
 
+    if let Some(Command::Bench(bench_args)) = &args.command {
+        let report = bench::run(&args, bench_args).await?;
+        bench::report(&report, args.output);
+        return Ok(ExitCode::SUCCESS);
+    }
+
     let transport = UPTransportZenoh::new(args.get_zenoh_config()?, "//horn_client/1/1/0")
         .await
         .map(Arc::new)?;
 
+    let command = args.command.clone().unwrap_or(Command::Demo);
+    if let Command::Status | Command::Watch { .. } = command {
+        let horn_status_uri = horn::horn_topic_uri(&args.service)?;
+        let mut rx_status =
+            status::subscribe_horn_status(transport.as_ref(), &horn_status_uri).await?;
+        if let Command::Watch { all } = command {
+            info!(
+                "Watching the status of the horn at [{}]",
+                horn_status_uri.to_uri(false)
+            );
+            status::watch(
+                rx_status,
+                args.output,
+                all,
+                Duration::from_millis(args.timeout.into()),
+            )
+            .await;
+        } else {
+            match tokio::time::timeout(Duration::from_millis(args.timeout.into()), rx_status.recv())
+                .await
+            {
+                Ok(Some(horn_status)) => output::print_horn_status(args.output, &horn_status),
+                _ => {
+                    error!("No horn status received within {} ms", args.timeout);
+                    return Ok(output::exit_code(UCode::DEADLINE_EXCEEDED));
+                }
+            }
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
     // The Zenoh transport happens to implement the
     // traits for UTransport and LocalUriProvider,
     // which is why it is used twice here.
     let rpc_client = InMemoryRpcClient::new(transport.clone(), transport).await?;
-    let activate_horn_uri = up_rust::UUri::try_from_parts(
-        HORN_SERVICE_AUTHORITY_NAME,
-        HORN_SERVICE_ENTITY_ID,
-        1,
-        ACTIVATE_HORN_RESOURCE_ID,
-    )?;
-    let deactivate_horn_uri = up_rust::UUri::try_from_parts(
-        HORN_SERVICE_AUTHORITY_NAME,
-        HORN_SERVICE_ENTITY_ID,
-        1,
-        DEACTIVATE_HORN_RESOURCE_ID,
-    )?;
-    let horn_request = ActivateHornRequest {
-        mode: HornMode::HM_SEQUENCED.into(),
-        command: vec![HornSequence {
-            horn_cycles: vec![
-                HornCycle {
-                    on_time: 100,
-                    off_time: 100,
-                    ..Default::default()
-                },
-                HornCycle {
-                    on_time: 200,
-                    off_time: 300,
-                    ..Default::default()
-                },
-                HornCycle {
-                    on_time: 100,
-                    off_time: 200,
-                    ..Default::default()
-                },
-                HornCycle {
-                    on_time: 10000,
-                    off_time: 500,
-                    ..Default::default()
-                },
-            ],
-            ..Default::default()
-        }],
-        ..Default::default()
+    let horn_service = HornServiceProxy::new(rpc_client, &args.service, args.timeout)?;
+
+    let code = match command {
+        Command::Activate {
+            request,
+            combination,
+        } => {
+            let mut request = activate_horn_request(request, args.patterns.as_deref())?;
+            if let Some(combination) = combination {
+                request.set_combination_mode(combination);
+            }
+            patterns::validate(&request).map_err(|e| format!("invalid horn request: {e}"))?;
+            let invocation = horn_service.activate(request).await?;
+            output::print_invocation(args.output, "ActivateHorn", &invocation);
+            invocation.outcome.code()
+        }
+        Command::Deactivate => {
+            let invocation = horn_service.deactivate().await?;
+            output::print_invocation(args.output, "DeactivateHorn", &invocation);
+            invocation.outcome.code()
+        }
+        Command::Demo => demo(&horn_service, args.output).await?,
+        Command::Scenario { file } => {
+            let scenario = Scenario::load(&file)?;
+            let results = scenario::run(&scenario, &horn_service, args.patterns.as_deref()).await;
+            scenario::report(&scenario, &results, args.output);
+            return Ok(scenario::exit_code(&results));
+        }
+        Command::Status | Command::Watch { .. } | Command::Bench(_) | Command::Patterns { .. } => {
+            unreachable!("handled above")
+        }
     };
+    Ok(output::exit_code(code))
+}
 
-    let payload = UPayload::try_from_protobuf(horn_request)?;
-    match rpc_client
-        .invoke_method(
-            activate_horn_uri.clone(),
-            CallOptions::for_rpc_request(1_000, None, None, None),
-            Some(payload),
-        )
-        .await
-    {
-        Ok(Some(payload)) => {
-            let response = payload.extract_protobuf::<ActivateHornResponse>()?;
-            info!("Activate Horn returned message: {}", response);
-        }
-        Ok(None) => info!("The activate horn request returned an empty response"),
-        Err(e) => error!("The activate horn request returned the error: {:?}", e),
+fn activate_horn_request(
+    args: ActivateArgs,
+    patterns_directory: Option<&Path>,
+) -> Result<ActivateHornRequest, Box<dyn std::error::Error>> {
+    if args.continuous {
+        Ok(RequestBuilder::continuous().build())
+    } else if let Some(name) = args.pattern {
+        let library = PatternLibrary::load(patterns_directory)?;
+        Ok(library.get(&name)?.request.clone())
+    } else if let Some(path) = args.file {
+        patterns::load_file(&path)
+    } else {
+        Ok(args
+            .sequence
+            .into_iter()
+            .fold(RequestBuilder::sequenced(), RequestBuilder::sequence)
+            .build())
     }
+}
+
+// Returns the first code other than OK returned by the service, if any.
+async fn demo(
+    horn_service: &HornServiceProxy,
+    format: OutputFormat,
+) -> Result<UCode, Box<dyn std::error::Error>> {
+    let mut invocations = Vec::with_capacity(4);
+    let horn_request = RequestBuilder::sequenced()
+        .sequence(
+            SequenceBuilder::new()
+                .cycle(100, 100)
+                .cycle(200, 300)
+                .cycle(100, 200)
+                .cycle(10000, 500),
+        )
+        .build();
+    let invocation = horn_service.activate(horn_request).await?;
+    output::print_invocation(format, "ActivateHorn", &invocation);
+    invocations.push(invocation);
 
     // Wait before deactivating the horn
     tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    let invocation = horn_service.deactivate().await?;
+    output::print_invocation(format, "DeactivateHorn", &invocation);
+    invocations.push(invocation);
 
-    let deactivate_request = DeactivateHornRequest::default();
-
-    let deactivate_payload = UPayload::try_from_protobuf(deactivate_request)?;
-
-    match rpc_client
-        .invoke_method(
-            deactivate_horn_uri.clone(),
-            CallOptions::for_rpc_request(1_000, None, None, None),
-            Some(deactivate_payload),
-        )
-        .await
-    {
-        Ok(Some(_)) => {
-            info!("The deactivate horn request returned successfully");
-        }
-        Ok(None) => error!("The deactivate horn request returned an empty response"),
-        Err(e) => error!("The deactivate horn request returned the error: {:?}", e),
-    }
-    let horn_request = ActivateHornRequest {
-        mode: HornMode::HM_CONTINUOUS.into(),
-        command: vec![HornSequence {
-            horn_cycles: vec![],
-            ..Default::default()
-        }],
-        ..Default::default()
-    };
-
-    let payload = UPayload::try_from_protobuf(horn_request)?;
-    match rpc_client
-        .invoke_method(
-            activate_horn_uri.clone(),
-            CallOptions::for_rpc_request(1_000, None, None, None),
-            Some(payload),
-        )
-        .await
-    {
-        Ok(Some(payload)) => {
-            let value = payload.extract_protobuf::<ActivateHornResponse>()?;
-            info!(
-                "Activate Horn returned message: {}",
-                value.status.unwrap().code
-            );
-        }
-        Ok(None) => error!("The activate horn request returned an empty response"),
-        Err(e) => error!("The activate horn request returned the error: {:?}", e),
-    }
+    let horn_request = RequestBuilder::continuous()
+        .sequence(SequenceBuilder::new())
+        .build();
+    let invocation = horn_service.activate(horn_request).await?;
+    output::print_invocation(format, "ActivateHorn", &invocation);
+    invocations.push(invocation);
 
     // Wait before deactivating the horn
     tokio::time::sleep(std::time::Duration::from_millis(4000)).await;
-
-    let deactivate_request = DeactivateHornRequest::default();
-
-    let deactivate_payload = UPayload::try_from_protobuf(deactivate_request)?;
-
-    match rpc_client
-        .invoke_method(
-            deactivate_horn_uri.clone(),
-            CallOptions::for_rpc_request(1_000, None, None, None),
-            Some(deactivate_payload),
-        )
-        .await
-    {
-        Ok(Some(_)) => {
-            info!("The deactivate horn request returned successfully");
-        }
-        Ok(None) => error!("The deactivate horn request returned an empty response"),
-        Err(e) => error!("The deactivate horn request returned the error: {:?}", e),
-    }
-    Ok(())
-}
-
-#[derive(clap::Parser, Clone, PartialEq, Eq, Hash, Debug)]
-pub struct Args {
-    #[arg(short, long, default_value = "zenoh-config.json5")]
-    /// A Zenoh configuration file.
-    config: PathBuf,
-}
-
-impl Args {
-    pub fn get_zenoh_config(&self) -> Result<zenoh_config::Config, Box<dyn std::error::Error>> {
-        // Load the config from file path
-        zenoh_config::Config::from_file(&self.config).map_err(|e| e as Box<dyn std::error::Error>)
-    }
+    let invocation = horn_service.deactivate().await?;
+    output::print_invocation(format, "DeactivateHorn", &invocation);
+    invocations.push(invocation);
+
+    Ok(invocations
+        .iter()
+        .map(|invocation| invocation.outcome.code())
+        .find(|code| *code != UCode::OK)
+        .unwrap_or(UCode::OK))
 }
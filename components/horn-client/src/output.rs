@@ -0,0 +1,159 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::process::ExitCode;
+use std::time::Duration;
+
+use horn_proto::horn_topics::HornStatus;
+use horn_proto::status::Status;
+use log::{error, info};
+use protobuf::well_known_types::duration::Duration as ProtoDuration;
+use protobuf::well_known_types::timestamp::Timestamp;
+use protobuf::{Enum, MessageFull};
+use serde_json::json;
+use up_rust::UCode;
+
+use crate::proxy::{Invocation, Outcome};
+use crate::status;
+
+/// Exit code of a scenario in which at least one step failed.
+pub(crate) const EXIT_SCENARIO_FAILED: u8 = 3;
+// Exit codes for the outcome of an invocation are offset by the
+// numeric value of the code (google.rpc.Code and uProtocol UCode)
+const EXIT_CODE_OFFSET: u8 = 10;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable log messages.
+    #[default]
+    Text,
+    /// One JSON document per line on stdout.
+    Json,
+}
+
+/// Maps the code of an invocation to the exit code of the process:
+/// 0 for `OK` and 10 plus the numeric value of the code otherwise.
+/// E.g. `INVALID_ARGUMENT` (3) results in 13 and `DEADLINE_EXCEEDED` (4) in 14.
+pub(crate) fn exit_code(code: UCode) -> ExitCode {
+    match code {
+        UCode::OK => ExitCode::SUCCESS,
+        code => ExitCode::from(EXIT_CODE_OFFSET + code.value() as u8),
+    }
+}
+
+pub(crate) fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// The JSON representation of an invocation of the Horn service method `method`.
+pub(crate) fn invocation_to_json(method: &str, invocation: &Invocation) -> serde_json::Value {
+    let code = invocation.outcome.code();
+    let mut json = json!({
+        "method": method,
+        "code": format!("{code:?}"),
+        "codeValue": code.value(),
+        "latencyMs": millis(invocation.latency),
+    });
+    match &invocation.outcome {
+        Outcome::Status(status) => {
+            json["message"] = json!(status.message);
+            if let Some(duration) = detail::<ProtoDuration>(status) {
+                let duration: Duration = duration.into();
+                json["plannedDurationMs"] = json!(millis(duration));
+            }
+            if let Some(end) = detail::<Timestamp>(status) {
+                // RFC 3339 according to the protobuf JSON mapping
+                json["plannedEnd"] = horn_proto::json::to_value(&end).unwrap_or_default();
+            }
+        }
+        Outcome::EmptyResponse => json["error"] = json!("empty response"),
+        Outcome::Error(e) => json["error"] = json!(format!("{e:?}")),
+    }
+    json
+}
+
+// The first detail of the given type of a status, e.g. the planned duration of an activation.
+fn detail<M: MessageFull>(status: &Status) -> Option<M> {
+    status
+        .details
+        .iter()
+        .find_map(|detail| detail.unpack::<M>().ok().flatten())
+}
+
+/// Reports the outcome of an invocation in the given format.
+/// The text format relies on the log messages of the proxy.
+pub(crate) fn print_invocation(format: OutputFormat, method: &str, invocation: &Invocation) {
+    match format {
+        OutputFormat::Text => {
+            info!(
+                "{method} returned {:?} after {:.1} ms",
+                invocation.outcome.code(),
+                millis(invocation.latency)
+            );
+            if let Outcome::Status(status) = &invocation.outcome {
+                if let Some(duration) = detail::<ProtoDuration>(status) {
+                    let duration: Duration = duration.into();
+                    info!("The horn plays for {:.1} ms", millis(duration));
+                }
+            }
+        }
+        OutputFormat::Json => println!("{}", invocation_to_json(method, invocation)),
+    }
+}
+
+/// The JSON representation of a status in the protobuf JSON mapping. Fields with
+/// default values are included, so that e.g. an inactive horn reports `"isActive": false`.
+pub(crate) fn horn_status_to_json(
+    horn_status: &HornStatus,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    horn_proto::json::to_value(horn_status)
+}
+
+pub(crate) fn print_horn_status(format: OutputFormat, horn_status: &HornStatus) {
+    match format {
+        OutputFormat::Text => info!("Horn status: {}", status::describe(horn_status)),
+        OutputFormat::Json => match horn_status_to_json(horn_status) {
+            Ok(json) => println!("{json}"),
+            Err(e) => error!("Failed to print the horn status as JSON: {e}"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The exit code clap uses for invalid arguments
+    const EXIT_USAGE: u8 = 2;
+
+    #[test]
+    fn exit_codes_are_offset_by_the_code() {
+        assert_eq!(exit_code(UCode::OK), ExitCode::SUCCESS);
+        assert_eq!(exit_code(UCode::INVALID_ARGUMENT), ExitCode::from(13));
+        assert_eq!(exit_code(UCode::RESOURCE_EXHAUSTED), ExitCode::from(18));
+    }
+
+    #[test]
+    fn exit_codes_of_codes_are_distinct() {
+        let mut exit_codes = vec![
+            ExitCode::FAILURE,
+            ExitCode::from(EXIT_USAGE),
+            ExitCode::from(EXIT_SCENARIO_FAILED),
+        ];
+        for code in UCode::VALUES {
+            let exit_code = exit_code(*code);
+            assert!(!exit_codes.contains(&exit_code), "{code:?}");
+            exit_codes.push(exit_code);
+        }
+    }
+}
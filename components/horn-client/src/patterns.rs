@@ -0,0 +1,313 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use horn_proto::horn_service::ActivateHornRequest;
+use horn_proto::horn_topics::HornMode;
+use horn_proto::pattern::format_sequence;
+use log::debug;
+
+use crate::output::OutputFormat;
+
+const BUILT_IN_PATTERNS: [(&str, &str); 3] = [
+    ("find-my-car", include_str!("../patterns/find-my-car.yaml")),
+    ("lock-chirp", include_str!("../patterns/lock-chirp.yaml")),
+    ("panic", include_str!("../patterns/panic.yaml")),
+];
+
+/// Where a horn pattern has been loaded from.
+#[derive(Clone, Debug)]
+pub(crate) enum PatternSource {
+    BuiltIn,
+    File(PathBuf),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Pattern {
+    pub source: PatternSource,
+    pub request: ActivateHornRequest,
+}
+
+/// The named horn patterns known to the client: the built-in presets
+/// and the pattern files of an optional directory, which take precedence.
+pub(crate) struct PatternLibrary {
+    patterns: BTreeMap<String, Pattern>,
+}
+
+impl PatternLibrary {
+    pub fn load(directory: Option<&Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut patterns = BTreeMap::new();
+        for (name, content) in BUILT_IN_PATTERNS {
+            let request = parse_yaml(content)
+                .map_err(|e| format!("invalid built-in horn pattern '{name}': {e}"))?;
+            patterns.insert(
+                name.to_string(),
+                Pattern {
+                    source: PatternSource::BuiltIn,
+                    request,
+                },
+            );
+        }
+
+        if let Some(directory) = directory {
+            for entry in std::fs::read_dir(directory)? {
+                let path = entry?.path();
+                if !is_pattern_file(&path) {
+                    continue;
+                }
+                let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                    continue;
+                };
+                debug!("Loading horn pattern '{name}' from {}", path.display());
+                let request = load_file(&path)?;
+                // the order of the directory entries is undefined, so a name
+                // given to several files has no well-defined pattern
+                if let Some(Pattern {
+                    source: PatternSource::File(other),
+                    ..
+                }) = patterns.get(name)
+                {
+                    return Err(format!(
+                        "horn pattern '{name}' is defined by both {} and {}",
+                        other.display(),
+                        path.display()
+                    )
+                    .into());
+                }
+                patterns.insert(
+                    name.to_string(),
+                    Pattern {
+                        source: PatternSource::File(path.clone()),
+                        request,
+                    },
+                );
+            }
+        }
+        Ok(Self { patterns })
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Pattern, String> {
+        self.patterns.get(name).ok_or_else(|| {
+            format!(
+                "unknown horn pattern '{name}', available patterns: {}",
+                self.patterns.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Pattern)> {
+        self.patterns.iter()
+    }
+}
+
+fn is_pattern_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("horn" | "json" | "yaml" | "yml")
+    )
+}
+
+/// Reads an `ActivateHornRequest` in the protobuf JSON mapping from a JSON or YAML file,
+/// or in the compact notation of `horn_proto::pattern` from a `.horn` file.
+pub(crate) fn load_file(path: &Path) -> Result<ActivateHornRequest, Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read horn pattern {}: {e}", path.display()))?;
+    let request = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("horn") => parse_notation(&content),
+        Some("json") => horn_proto::json::from_str(&content).map_err(|e| e.to_string()),
+        _ => parse_yaml(&content),
+    };
+    request.map_err(|e| format!("invalid horn pattern {}: {e}", path.display()).into())
+}
+
+fn parse_yaml(content: &str) -> Result<ActivateHornRequest, String> {
+    // YAML is a superset of JSON, so the document is converted to JSON
+    // and parsed according to the protobuf JSON mapping
+    let json = serde_yaml::from_str::<serde_json::Value>(content).map_err(|e| e.to_string())?;
+    horn_proto::json::from_value(&json).map_err(|e| e.to_string())
+}
+
+// Lines starting with '#' are comments, the remaining lines are joined to one request.
+fn parse_notation(content: &str) -> Result<ActivateHornRequest, String> {
+    let notation = content
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join(" ");
+    horn_proto::pattern::parse_request(&notation).map_err(|e| e.to_string())
+}
+
+/// Checks a request against the limits declared for the Horn service.
+pub(crate) fn validate(request: &ActivateHornRequest) -> Result<(), String> {
+    request.validate().map_err(|e| e.to_string())?;
+    match request.mode.enum_value() {
+        Ok(HornMode::HM_CONTINUOUS) => Ok(()),
+        Ok(HornMode::HM_SEQUENCED) if request.command.is_empty() => {
+            Err("a sequenced horn request requires at least one sequence".into())
+        }
+        Ok(HornMode::HM_SEQUENCED) => Ok(()),
+        Ok(mode) => Err(format!("unsupported horn mode {mode:?}")),
+        Err(value) => Err(format!("unknown horn mode value {value}")),
+    }
+}
+
+/// Prints the names of the available patterns to stdout.
+pub(crate) fn list(library: &PatternLibrary, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => library.iter().for_each(|(name, _)| println!("{name}")),
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::Value::from(
+                library
+                    .iter()
+                    .map(|(name, _)| name.clone())
+                    .collect::<Vec<_>>()
+            )
+        ),
+    }
+}
+
+/// Prints a description of a pattern to stdout.
+pub(crate) fn describe(
+    name: &str,
+    pattern: &Pattern,
+    format: OutputFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let request = &pattern.request;
+    if format == OutputFormat::Json {
+        let json = serde_json::json!({
+            "name": name,
+            "source": match &pattern.source {
+                PatternSource::BuiltIn => "built-in".to_string(),
+                PatternSource::File(path) => path.display().to_string(),
+            },
+            "request": horn_proto::json::to_value(request)?,
+            "durationMs": request.duration().as_millis() as u64,
+            "error": validate(request).err(),
+        });
+        println!("{json}");
+        return Ok(());
+    }
+
+    match &pattern.source {
+        PatternSource::BuiltIn => println!("{name} (built-in)"),
+        PatternSource::File(path) => println!("{name} ({})", path.display()),
+    }
+    match request.mode.enum_value() {
+        Ok(HornMode::HM_SEQUENCED) => {
+            println!("  mode: sequenced");
+            for (index, sequence) in request.command.iter().enumerate() {
+                println!("  sequence {}: {}", index + 1, format_sequence(sequence));
+            }
+            println!("  duration: {} ms", request.duration().as_millis());
+        }
+        Ok(HornMode::HM_CONTINUOUS) => println!("  mode: continuous"),
+        _ => println!("  mode: {:?}", request.mode),
+    }
+    if let Err(e) = validate(request) {
+        println!("  invalid: {e}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEEP_JSON: &str = r#"{"mode": "HM_SEQUENCED", "command": [{"hornCycles": [{"onTime": 100, "offTime": 50}]}]}"#;
+    const BEEP_YAML: &str = "\
+mode: HM_SEQUENCED
+command:
+  - hornCycles:
+      - onTime: 100
+        offTime: 50
+";
+
+    fn pattern_directory(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let directory = tempfile::tempdir().unwrap();
+        for (name, content) in files {
+            std::fs::write(directory.path().join(name), content).unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    fn built_in_patterns_are_valid() {
+        let library = PatternLibrary::load(None).unwrap();
+        for (name, _) in BUILT_IN_PATTERNS {
+            let pattern = library.get(name).unwrap();
+            assert!(matches!(pattern.source, PatternSource::BuiltIn));
+            assert_eq!(validate(&pattern.request), Ok(()), "pattern {name}");
+        }
+        assert!(library.get("unknown").is_err());
+    }
+
+    #[test]
+    fn json_and_yaml_patterns_are_loaded() {
+        let directory = pattern_directory(&[
+            ("beep.json", BEEP_JSON),
+            ("beep-yaml.yaml", BEEP_YAML),
+            ("beep-notation.horn", "# a single beep\n100/50\n"),
+            ("notes.txt", "not a pattern"),
+        ]);
+        let library = PatternLibrary::load(Some(directory.path())).unwrap();
+        let request = &library.get("beep").unwrap().request;
+        assert_eq!(request.mode.enum_value(), Ok(HornMode::HM_SEQUENCED));
+        assert_eq!(format_sequence(&request.command[0]), "100/50");
+        assert_eq!(&library.get("beep-yaml").unwrap().request, request);
+        assert_eq!(&library.get("beep-notation").unwrap().request, request);
+        assert!(library.get("notes").is_err());
+        assert!(matches!(
+            library.get("beep").unwrap().source,
+            PatternSource::File(_)
+        ));
+    }
+
+    #[test]
+    fn invalid_patterns_are_reported() {
+        let directory = pattern_directory(&[("broken.yaml", "mode: HM_SEQUENCED\nvolume: 11\n")]);
+        let error = PatternLibrary::load(Some(directory.path()))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("broken.yaml"), "{error}");
+
+        // parsed, but below the limits of the Horn service
+        let directory = pattern_directory(&[("short.yaml", &BEEP_YAML.replace("100", "10"))]);
+        let request = load_file(&directory.path().join("short.yaml")).unwrap();
+        assert!(validate(&request).is_err());
+        let empty = parse_yaml("mode: HM_SEQUENCED").unwrap();
+        assert!(validate(&empty).is_err());
+    }
+
+    #[test]
+    fn files_take_precedence_over_built_in_patterns() {
+        let directory = pattern_directory(&[("panic.json", BEEP_JSON)]);
+        let library = PatternLibrary::load(Some(directory.path())).unwrap();
+        let pattern = library.get("panic").unwrap();
+        assert!(matches!(pattern.source, PatternSource::File(_)));
+        assert_eq!(format_sequence(&pattern.request.command[0]), "100/50");
+    }
+
+    #[test]
+    fn names_of_several_files_are_rejected() {
+        let directory = pattern_directory(&[("beep.json", BEEP_JSON), ("beep.yaml", BEEP_YAML)]);
+        let error = PatternLibrary::load(Some(directory.path()))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("'beep' is defined by both"), "{error}");
+    }
+}
@@ -0,0 +1,117 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use horn_proto::horn_service::{ActivateHornRequest, DeactivateHornRequest};
+use horn_proto::rpc::{HornClient, RpcError};
+use horn_proto::status::Status;
+use log::{error, info};
+use protobuf::Enum;
+use up_rust::communication::{InMemoryRpcClient, ServiceInvocationError};
+use up_rust::{UCode, UStatus};
+
+/// The outcome of a single invocation of a method of the Horn service.
+pub(crate) struct Invocation {
+    /// The time between sending the request and receiving the response or error.
+    pub latency: Duration,
+    pub outcome: Outcome,
+}
+
+pub(crate) enum Outcome {
+    /// The service responded with the given status.
+    Status(Status),
+    /// The service responded without a payload.
+    EmptyResponse,
+    /// The invocation failed before the service responded.
+    Error(ServiceInvocationError),
+}
+
+impl Outcome {
+    /// The canonical code of the outcome, i.e. the code of the returned
+    /// status or the code corresponding to the invocation error.
+    pub fn code(&self) -> UCode {
+        match self {
+            Outcome::Status(status) => UCode::from_i32(status.code).unwrap_or(UCode::UNKNOWN),
+            Outcome::EmptyResponse => UCode::UNKNOWN,
+            Outcome::Error(e) => UStatus::from(e.clone()).code.enum_value_or_default(),
+        }
+    }
+}
+
+// Invokes the methods of the Horn service at the given authority.
+pub(crate) struct HornServiceProxy {
+    client: HornClient,
+}
+
+impl HornServiceProxy {
+    pub fn new(
+        rpc_client: InMemoryRpcClient,
+        authority: &str,
+        timeout: u32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let client = HornClient::new(Arc::new(rpc_client), authority, timeout)?;
+        Ok(Self { client })
+    }
+
+    pub async fn activate(
+        &self,
+        request: ActivateHornRequest,
+    ) -> Result<Invocation, Box<dyn std::error::Error>> {
+        let start = Instant::now();
+        let result = self.client.activate_horn(request).await;
+        let latency = start.elapsed();
+        let outcome = match result {
+            Ok(response) => {
+                info!("Activate Horn returned message: {}", response);
+                Outcome::Status(response.status.unwrap_or_default())
+            }
+            Err(RpcError::EmptyResponse) => {
+                error!("The activate horn request returned an empty response");
+                Outcome::EmptyResponse
+            }
+            Err(RpcError::Invocation(e)) => {
+                error!("The activate horn request returned the error: {:?}", e);
+                Outcome::Error(e)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Invocation { latency, outcome })
+    }
+
+    pub async fn deactivate(&self) -> Result<Invocation, Box<dyn std::error::Error>> {
+        let start = Instant::now();
+        let result = self
+            .client
+            .deactivate_horn(DeactivateHornRequest::default())
+            .await;
+        let latency = start.elapsed();
+        let outcome = match result {
+            Ok(response) => {
+                info!("The deactivate horn request returned successfully");
+                Outcome::Status(response.status.unwrap_or_default())
+            }
+            Err(RpcError::EmptyResponse) => {
+                error!("The deactivate horn request returned an empty response");
+                Outcome::EmptyResponse
+            }
+            Err(RpcError::Invocation(e)) => {
+                error!("The deactivate horn request returned the error: {:?}", e);
+                Outcome::Error(e)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Invocation { latency, outcome })
+    }
+}
@@ -0,0 +1,400 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use horn_proto::combination::CombinationMode;
+use horn_proto::horn_service::ActivateHornRequest;
+use horn_proto::pattern::RequestBuilder;
+use log::{info, warn};
+use protobuf::Enum;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::time::Instant;
+use up_rust::UCode;
+
+use crate::output::{self, OutputFormat};
+use crate::patterns::{self, PatternLibrary};
+use crate::proxy::HornServiceProxy;
+
+/// A list of timed steps invoking the Horn service, read from a YAML or JSON file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Scenario {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Step {
+    /// The offset of the step from the start of the scenario in milliseconds.
+    pub at: u64,
+    #[serde(default)]
+    pub activate: Option<ActivateStep>,
+    #[serde(default)]
+    pub deactivate: Option<DeactivateStep>,
+    /// The expected response code given by its name, e.g. `OK` or `INVALID_ARGUMENT`.
+    #[serde(default = "default_expectation", deserialize_with = "deserialize_code")]
+    pub expect: UCode,
+}
+
+/// The request of an activation step, given in one of the ways supported by `activate`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ActivateStep {
+    #[serde(default)]
+    pub continuous: bool,
+    #[serde(default)]
+    pub sequence: Vec<String>,
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+    /// An inline ActivateHornRequest in the protobuf JSON mapping.
+    #[serde(default, deserialize_with = "deserialize_request")]
+    pub request: Option<ActivateHornRequest>,
+    /// Whether the request is checked against the limits of the Horn service before it is sent.
+    #[serde(default = "default_validate")]
+    pub validate: bool,
+    /// How the request is combined with a running request, e.g. `queue`.
+    #[serde(default, deserialize_with = "deserialize_combination_mode")]
+    pub combination: Option<CombinationMode>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DeactivateStep {}
+
+fn default_expectation() -> UCode {
+    UCode::OK
+}
+
+fn deserialize_code<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<UCode, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    UCode::from_str(&name)
+        .ok_or_else(|| serde::de::Error::custom(format!("unknown response code '{name}'")))
+}
+
+fn deserialize_request<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<ActivateHornRequest>, D::Error> {
+    horn_proto::json::deserialize(deserializer).map(Some)
+}
+
+fn deserialize_combination_mode<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<CombinationMode>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    name.parse().map(Some).map_err(serde::de::Error::custom)
+}
+
+fn default_validate() -> bool {
+    true
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read scenario {}: {e}", path.display()))?;
+        let mut scenario: Scenario = serde_yaml::from_str(&content)
+            .map_err(|e| format!("invalid scenario {}: {e}", path.display()))?;
+        for (index, step) in scenario.steps.iter().enumerate() {
+            if step.activate.is_some() == step.deactivate.is_some() {
+                return Err(format!(
+                    "invalid scenario {}: step {} needs either 'activate' or 'deactivate'",
+                    path.display(),
+                    index + 1
+                )
+                .into());
+            }
+        }
+        scenario.steps.sort_by_key(|step| step.at);
+        Ok(scenario)
+    }
+}
+
+impl ActivateStep {
+    fn request(
+        &self,
+        patterns_directory: Option<&Path>,
+    ) -> Result<ActivateHornRequest, Box<dyn std::error::Error>> {
+        let mut request = self.base_request(patterns_directory)?;
+        if let Some(combination) = self.combination {
+            request.set_combination_mode(combination);
+        }
+        Ok(request)
+    }
+
+    fn base_request(
+        &self,
+        patterns_directory: Option<&Path>,
+    ) -> Result<ActivateHornRequest, Box<dyn std::error::Error>> {
+        if let Some(request) = &self.request {
+            return Ok(request.clone());
+        }
+        if let Some(name) = &self.pattern {
+            let library = PatternLibrary::load(patterns_directory)?;
+            return Ok(library.get(name)?.request.clone());
+        }
+        if let Some(path) = &self.file {
+            return patterns::load_file(path);
+        }
+        if self.continuous {
+            return Ok(RequestBuilder::continuous().build());
+        }
+        let mut builder = RequestBuilder::sequenced();
+        for sequence in &self.sequence {
+            builder = builder.sequence(crate::config::parse_horn_sequence(sequence)?);
+        }
+        Ok(builder.build())
+    }
+}
+
+/// The result of a single step of a scenario.
+pub(crate) struct StepResult {
+    pub at: u64,
+    pub action: &'static str,
+    pub latency: Option<Duration>,
+    pub expected: UCode,
+    /// The received code, or a description of why the step could not be executed.
+    pub actual: Result<UCode, String>,
+}
+
+impl StepResult {
+    pub fn passed(&self) -> bool {
+        self.actual == Ok(self.expected)
+    }
+}
+
+/// The exit code of the process for the results of a scenario:
+/// 0 if all steps passed and `EXIT_SCENARIO_FAILED` otherwise.
+pub(crate) fn exit_code(results: &[StepResult]) -> ExitCode {
+    if results.iter().all(StepResult::passed) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(output::EXIT_SCENARIO_FAILED)
+    }
+}
+
+/// Executes the steps of a scenario at their points in time and reports whether
+/// the Horn service responded with the expected codes.
+pub(crate) async fn run(
+    scenario: &Scenario,
+    horn_service: &HornServiceProxy,
+    patterns_directory: Option<&Path>,
+) -> Vec<StepResult> {
+    if let Some(name) = &scenario.name {
+        info!("Running scenario '{name}'");
+    }
+    let start = Instant::now();
+    let mut results = Vec::with_capacity(scenario.steps.len());
+    for step in &scenario.steps {
+        let due = start + Duration::from_millis(step.at);
+        if Instant::now() > due {
+            warn!(
+                "Step at {} ms starts {} ms late",
+                step.at,
+                (Instant::now() - due).as_millis()
+            );
+        }
+        tokio::time::sleep_until(due).await;
+
+        let (action, invocation) = match &step.activate {
+            Some(activate) => {
+                let request = activate.request(patterns_directory).and_then(|request| {
+                    if activate.validate {
+                        patterns::validate(&request)?;
+                    }
+                    Ok(request)
+                });
+                match request {
+                    Ok(request) => ("activate", horn_service.activate(request).await),
+                    Err(e) => ("activate", Err(e)),
+                }
+            }
+            None => ("deactivate", horn_service.deactivate().await),
+        };
+        results.push(StepResult {
+            at: step.at,
+            action,
+            latency: invocation
+                .as_ref()
+                .ok()
+                .map(|invocation| invocation.latency),
+            expected: step.expect,
+            actual: invocation
+                .map(|invocation| invocation.outcome.code())
+                .map_err(|e| e.to_string()),
+        });
+    }
+    results
+}
+
+/// Prints one line per step and a summary to stdout.
+pub(crate) fn report(scenario: &Scenario, results: &[StepResult], format: OutputFormat) {
+    let failed = results.iter().filter(|result| !result.passed()).count();
+    if format == OutputFormat::Json {
+        let steps: Vec<_> = results
+            .iter()
+            .map(|result| {
+                json!({
+                    "at": result.at,
+                    "action": result.action,
+                    "expected": format!("{:?}", result.expected),
+                    "actual": result.actual.as_ref().map(|code| format!("{code:?}")).ok(),
+                    "error": result.actual.as_ref().err(),
+                    "latencyMs": result.latency.map(output::millis),
+                    "passed": result.passed(),
+                })
+            })
+            .collect();
+        let json = json!({
+            "scenario": scenario.name,
+            "steps": steps,
+            "passed": results.len() - failed,
+            "failed": failed,
+        });
+        println!("{json}");
+        return;
+    }
+
+    for (index, result) in results.iter().enumerate() {
+        let verdict = if result.passed() { "PASS" } else { "FAIL" };
+        let actual = match &result.actual {
+            Ok(code) => format!("{code:?}"),
+            Err(e) => format!("not executed ({e})"),
+        };
+        println!(
+            "{verdict} step {} at {} ms: {} expected {:?}, got {actual}",
+            index + 1,
+            result.at,
+            result.action,
+            result.expected
+        );
+    }
+    println!(
+        "{} of {} steps passed",
+        results.len() - failed,
+        results.len()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use horn_proto::horn_topics::HornMode;
+
+    fn load(content: &str) -> Result<Scenario, String> {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("scenario.yaml");
+        std::fs::write(&path, content).unwrap();
+        Scenario::load(&path).map_err(|e| e.to_string())
+    }
+
+    fn result(expected: UCode, actual: Result<UCode, String>) -> StepResult {
+        StepResult {
+            at: 0,
+            action: "activate",
+            latency: None,
+            expected,
+            actual,
+        }
+    }
+
+    #[test]
+    fn steps_are_parsed_and_ordered_by_time() {
+        let scenario = load(
+            "\
+name: queueing
+steps:
+  - at: 500
+    deactivate: {}
+  - at: 0
+    activate:
+      sequence: [\"100/50\"]
+      combination: queue
+  - at: 100
+    activate:
+      request: { mode: HM_CONTINUOUS }
+      validate: false
+    expect: FAILED_PRECONDITION
+",
+        )
+        .unwrap();
+        assert_eq!(scenario.name.as_deref(), Some("queueing"));
+        let at: Vec<_> = scenario.steps.iter().map(|step| step.at).collect();
+        assert_eq!(at, [0, 100, 500]);
+
+        let first = scenario.steps[0].activate.as_ref().unwrap();
+        assert_eq!(scenario.steps[0].expect, UCode::OK);
+        assert_eq!(first.combination, Some(CombinationMode::Queue));
+        let request = first.request(None).unwrap();
+        assert_eq!(request.combination_mode(), Some(CombinationMode::Queue));
+        assert_eq!(request.command.len(), 1);
+
+        let second = scenario.steps[1].activate.as_ref().unwrap();
+        assert_eq!(scenario.steps[1].expect, UCode::FAILED_PRECONDITION);
+        assert!(!second.validate);
+        let request = second.request(None).unwrap();
+        assert_eq!(request.mode.enum_value(), Ok(HornMode::HM_CONTINUOUS));
+        assert!(scenario.steps[2].deactivate.is_some());
+    }
+
+    #[test]
+    fn malformed_steps_are_rejected() {
+        for (scenario, error) in [
+            ("steps:\n  - at: 0\n", "needs either"),
+            (
+                "steps:\n  - at: 0\n    activate: {}\n    deactivate: {}\n",
+                "needs either",
+            ),
+            ("steps:\n  - deactivate: {}\n", "missing field `at`"),
+            ("steps:\n  - at: 0\n    honk: {}\n", "unknown field `honk`"),
+            (
+                "steps:\n  - at: 0\n    deactivate: {}\n    expect: LOUD\n",
+                "unknown response code 'LOUD'",
+            ),
+            (
+                "steps:\n  - at: 0\n    activate:\n      combination: mix\n",
+                "mix",
+            ),
+        ] {
+            let actual = load(scenario).err().unwrap();
+            assert!(actual.contains(error), "{actual} for {scenario:?}");
+        }
+    }
+
+    #[test]
+    fn steps_pass_with_the_expected_code_only() {
+        assert!(result(UCode::OK, Ok(UCode::OK)).passed());
+        assert!(result(UCode::RESOURCE_EXHAUSTED, Ok(UCode::RESOURCE_EXHAUSTED)).passed());
+        assert!(!result(UCode::OK, Ok(UCode::INVALID_ARGUMENT)).passed());
+        assert!(!result(UCode::OK, Err("invalid horn sequence".to_string())).passed());
+    }
+
+    #[test]
+    fn failed_steps_fail_the_scenario() {
+        let passed = || result(UCode::OK, Ok(UCode::OK));
+        let failed = result(UCode::OK, Ok(UCode::UNAVAILABLE));
+        assert_eq!(exit_code(&[]), ExitCode::SUCCESS);
+        assert_eq!(exit_code(&[passed(), passed()]), ExitCode::SUCCESS);
+        assert_eq!(
+            exit_code(&[passed(), failed]),
+            ExitCode::from(output::EXIT_SCENARIO_FAILED)
+        );
+    }
+}
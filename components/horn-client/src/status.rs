@@ -0,0 +1,134 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use horn_proto::horn_topics::{HornMode, HornStatus};
+use log::{error, warn};
+use serde_json::json;
+use tokio::sync::mpsc::{Receiver, Sender};
+use up_rust::{UListener, UMessage, UStatus, UTransport, UUri};
+
+use crate::output::{self, OutputFormat};
+
+// Forwards the HornStatus messages published by the Horn service to a channel.
+struct HornStatusListener {
+    tx_status: Sender<HornStatus>,
+}
+
+#[async_trait::async_trait]
+impl UListener for HornStatusListener {
+    async fn on_receive(&self, msg: UMessage) {
+        match msg.extract_protobuf::<HornStatus>() {
+            Ok(status) => {
+                let _ = self.tx_status.send(status).await;
+            }
+            Err(e) => warn!("Received a horn status which could not be decoded: {e}"),
+        }
+    }
+}
+
+/// Registers a listener for the status topic of the Horn service and returns the
+/// channel on which the received status updates are delivered.
+pub(crate) async fn subscribe_horn_status(
+    transport: &dyn UTransport,
+    topic: &UUri,
+) -> Result<Receiver<HornStatus>, UStatus> {
+    let (tx_status, rx_status) = tokio::sync::mpsc::channel(16);
+    transport
+        .register_listener(topic, None, Arc::new(HornStatusListener { tx_status }))
+        .await?;
+    Ok(rx_status)
+}
+
+/// Prints the received status updates until the subscription ends, either as one
+/// line of text or one JSON line per update, each with the time since the start.
+/// Unless `all` is set, updates equal to the previous status are skipped. Since the
+/// Horn service repeats its status, a warning is logged whenever none arrives within `timeout`.
+pub(crate) async fn watch(
+    mut rx_status: Receiver<HornStatus>,
+    format: OutputFormat,
+    all: bool,
+    timeout: Duration,
+) {
+    let start = Instant::now();
+    let mut previous: Option<HornStatus> = None;
+    loop {
+        let horn_status = match tokio::time::timeout(timeout, rx_status.recv()).await {
+            Ok(Some(horn_status)) => horn_status,
+            Ok(None) => break,
+            Err(_) => {
+                warn!(
+                    "No horn status received within {} ms, is the Horn service running?",
+                    timeout.as_millis()
+                );
+                continue;
+            }
+        };
+        if !all && previous.as_ref() == Some(&horn_status) {
+            continue;
+        }
+        let elapsed = start.elapsed();
+        match format {
+            OutputFormat::Text => println!(
+                "{:>10.3} s  {}",
+                elapsed.as_secs_f64(),
+                describe(&horn_status)
+            ),
+            OutputFormat::Json => match output::horn_status_to_json(&horn_status) {
+                Ok(status) => println!(
+                    "{}",
+                    json!({ "elapsedMs": output::millis(elapsed), "status": status })
+                ),
+                Err(e) => error!("Failed to print the horn status as JSON: {e}"),
+            },
+        }
+        previous = Some(horn_status);
+    }
+    warn!("The subscription to the horn status ended");
+}
+
+/// A one-line description of a status, e.g.
+/// `active, sequenced, sequence 2 of 3, 4 cycles remaining, priority 0`.
+pub(crate) fn describe(horn_status: &HornStatus) -> String {
+    let mut parts = Vec::new();
+    if horn_status.is_active {
+        parts.push("active".to_string());
+        match horn_status.mode.enum_value() {
+            Ok(HornMode::HM_SEQUENCED) => {
+                parts.push("sequenced".to_string());
+                match (horn_status.current_sequence, horn_status.total_sequences) {
+                    (Some(current), Some(total)) => {
+                        parts.push(format!("sequence {current} of {total}"))
+                    }
+                    (Some(current), None) => parts.push(format!("sequence {current}")),
+                    _ => {}
+                }
+                if let Some(remaining) = horn_status.remaining_cycles {
+                    parts.push(format!("{remaining} cycles remaining"));
+                }
+            }
+            Ok(HornMode::HM_CONTINUOUS) => parts.push("continuous".to_string()),
+            Ok(mode) => parts.push(format!("{mode:?}")),
+            Err(value) => parts.push(format!("unknown mode {value}")),
+        }
+        parts.push(format!("priority {}", horn_status.priority));
+    } else {
+        parts.push("inactive".to_string());
+    }
+    if horn_status.is_fault_active {
+        parts.push("FAULT".to_string());
+    }
+    parts.join(", ")
+}
@@ -14,14 +14,690 @@
 //extern crate prost_build;
 extern crate protobuf_codegen;
 
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use protobuf::descriptor::field_descriptor_proto::{Label, Type};
+use protobuf::descriptor::{FieldDescriptorProto, FileDescriptorProto};
+use protobuf::{CodedInputStream, UnknownValueRef};
+
+// The definitions shared by all services
+const COMMON_INPUTS: [&str; 4] = [
+    "uprotocol/uoptions.proto",
+    "uservices_options.proto",
+    "units.proto",
+    "google/rpc/status.proto",
+];
+
+// The definitions of the uServices by the cargo feature selecting them. The messages of these
+// files get validators, and their services get descriptors and a typed client and service trait.
+const SERVICES: [(&str, &[&str]); 2] = [
+    (
+        "horn",
+        &[
+            "vehicle/body/horn/v1/horn_service.proto",
+            "vehicle/body/horn/v1/horn_topics.proto",
+            "extensions/horn/v1/horn_extensions.proto",
+        ],
+    ),
+    ("chime", &["example/chime/v1/chime_service.proto"]),
+];
+
+// The field options declared in uservices_options.proto
+const READONLY: u32 = 52401;
+const WRITEONLY: u32 = 52402;
+const UNIT: u32 = 52403;
+const MAX_VALUE: u32 = 52404;
+const MIN_VALUE: u32 = 52405;
+
+// The service and method options declared in uprotocol/uoptions.proto
+const SERVICE_ID: u32 = 51100;
+const SERVICE_NAME: u32 = 51101;
+const SERVICE_VERSION_MAJOR: u32 = 51102;
+const SERVICE_VERSION_MINOR: u32 = 51103;
+const PUBLISH_TOPIC: u32 = 51105;
+const METHOD_ID: u32 = 51200;
+
+// Constraints which cannot be expressed with the field options, hand-written in src/validate.rs
+const MANUAL_CHECKS: [(&str, &str); 1] = [(
+    ".vehicle.body.horn.v1.ActivateHornRequest",
+    "check_sequence_count",
+)];
+
 fn main() -> Result<(), Box<dyn std::error::Error>>{
+    let service_files: Vec<&str> = SERVICES
+        .iter()
+        .filter(|(feature, _)| is_enabled(feature))
+        .flat_map(|(_, files)| files.iter().copied())
+        .collect();
+    // The helpers of the generated code are compiled only if any service is enabled
+    println!("cargo:rustc-check-cfg=cfg(uservices)");
+    if !service_files.is_empty() {
+        println!("cargo:rustc-cfg=uservices");
+    }
+    let inputs: Vec<String> = COMMON_INPUTS
+        .iter()
+        .chain(&service_files)
+        .map(|file| format!("proto/{file}"))
+        .collect();
+
     protobuf_codegen::Codegen::new()
         .protoc()
         // use vendored protoc instead of relying on user provided protobuf installation
         .protoc_path(&protoc_bin_vendored::protoc_bin_path().unwrap())
         .include("proto/")
-        .inputs(["proto/uprotocol/uoptions.proto", "proto/uservices_options.proto", "proto/units.proto", "proto/google/rpc/status.proto", "proto/vehicle/body/horn/v1/horn_service.proto", "proto/vehicle/body/horn/v1/horn_topics.proto", ])
+        .inputs(&inputs)
         .cargo_out_dir("uservice")
         .run_from_script();
+
+    let parsed = protobuf_parse::Parser::new()
+        .protoc()
+        .protoc_path(&protoc_bin_vendored::protoc_bin_path().unwrap())
+        .include("proto/")
+        .inputs(&inputs)
+        .parse_and_typecheck()?;
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+    std::fs::write(
+        out_dir.join("validate.rs"),
+        generate_validators(&parsed.file_descriptors, &service_files)?,
+    )?;
+    std::fs::write(
+        out_dir.join("descriptors.rs"),
+        generate_descriptors(&parsed.file_descriptors, &service_files)?,
+    )?;
+    std::fs::write(
+        out_dir.join("rpc.rs"),
+        generate_rpc(&parsed.file_descriptors, &service_files)?,
+    )?;
     Ok(())
 }
+
+fn is_enabled(feature: &str) -> bool {
+    let variable = format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+    std::env::var_os(variable).is_some()
+}
+
+/// The constraints of a field declared with the options of uservices_options.proto.
+#[derive(Default)]
+struct Constraints {
+    readonly: bool,
+    writeonly: bool,
+    unit: Option<String>,
+    min_value: Option<f64>,
+    max_value: Option<f64>,
+}
+
+impl Constraints {
+    fn of(field: &FieldDescriptorProto, units: &HashMap<i32, String>) -> Self {
+        let options = field.options.special_fields.unknown_fields();
+        let flag = |number| matches!(options.get(number), Some(UnknownValueRef::Varint(value)) if value != 0);
+        let double = |number| match options.get(number) {
+            Some(UnknownValueRef::Fixed64(bits)) => Some(f64::from_bits(bits)),
+            _ => None,
+        };
+        let unit = match options.get(UNIT) {
+            Some(UnknownValueRef::Varint(value)) => i32::try_from(value)
+                .ok()
+                .and_then(|value| units.get(&value).cloned()),
+            _ => None,
+        };
+        Constraints {
+            readonly: flag(READONLY),
+            writeonly: flag(WRITEONLY),
+            unit,
+            min_value: double(MIN_VALUE),
+            max_value: double(MAX_VALUE),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.readonly
+            && !self.writeonly
+            && self.unit.is_none()
+            && self.min_value.is_none()
+            && self.max_value.is_none()
+    }
+}
+
+// The Rust types of the messages by their full protobuf names, e.g. `crate::horn_topics::HornCycle`
+// for `.vehicle.body.horn.v1.HornCycle`, as generated by protobuf-codegen.
+fn rust_types(files: &[&FileDescriptorProto]) -> Result<HashMap<String, String>, String> {
+    let mut rust_types = HashMap::new();
+    for file in files {
+        let module = Path::new(file.name())
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| format!("invalid file name {}", file.name()))?;
+        for message in &file.message_type {
+            rust_types.insert(
+                format!(".{}.{}", file.package(), message.name()),
+                format!("crate::{module}::{}", message.name()),
+            );
+        }
+    }
+    Ok(rust_types)
+}
+
+fn option_literal<T: std::fmt::Debug>(value: &Option<T>) -> String {
+    match value {
+        Some(value) => format!("Some({value:?})"),
+        None => "None".to_string(),
+    }
+}
+
+// Generates a table of the declared constraints and a validate() method checking the
+// bounds for every message in the service files, which descends into nested messages.
+// The readonly and writeonly flags only end up in the table.
+fn generate_validators(
+    files: &[FileDescriptorProto],
+    service_files: &[&str],
+) -> Result<String, String> {
+    let units: HashMap<i32, String> = files
+        .iter()
+        .flat_map(|file| file.enum_type.iter())
+        .filter(|enum_type| enum_type.name() == "Units")
+        .flat_map(|enum_type| enum_type.value.iter())
+        .map(|value| (value.number(), value.name().to_string()))
+        .collect();
+    let validated: Vec<_> = files
+        .iter()
+        .filter(|file| service_files.contains(&file.name()))
+        .collect();
+    let rust_types = rust_types(&validated)?;
+    let mut packages: Vec<String> = validated
+        .iter()
+        .map(|file| format!("`{}`", file.package()))
+        .collect();
+    packages.sort();
+    packages.dedup();
+    let packages = if packages.is_empty() {
+        "no package, as no service is enabled".to_string()
+    } else {
+        packages.join(", ")
+    };
+
+    let mut table = String::new();
+    let mut impls = String::new();
+    for file in &validated {
+        for message in &file.message_type {
+            let full_name = format!(".{}.{}", file.package(), message.name());
+            let mut checks = String::new();
+            for (_, manual_check) in MANUAL_CHECKS
+                .iter()
+                .filter(|(message, _)| *message == full_name)
+            {
+                writeln!(checks, "        {manual_check}(self, path)?;").unwrap();
+            }
+            for field in &message.field {
+                let name = field.name();
+                let constraints = Constraints::of(field, &units);
+                if !constraints.is_empty() {
+                    writeln!(
+                        table,
+                        "    FieldConstraint {{ message: {:?}, field: {name:?}, min_value: {}, max_value: {}, unit: {}, readonly: {}, writeonly: {} }},",
+                        &full_name[1..],
+                        option_literal(&constraints.min_value),
+                        option_literal(&constraints.max_value),
+                        option_literal(&constraints.unit),
+                        constraints.readonly,
+                        constraints.writeonly
+                    )
+                    .unwrap();
+                }
+
+                let repeated = field.label() == Label::LABEL_REPEATED;
+                if field.type_() == Type::TYPE_MESSAGE {
+                    if !rust_types.contains_key(field.type_name()) {
+                        continue;
+                    }
+                    if repeated {
+                        writeln!(
+                            checks,
+                            "        for (index, item) in self.{name}.iter().enumerate() {{\n            item.validate_at(&format!(\"{{}}[{{index}}]\", field_path(path, {name:?})))?;\n        }}"
+                        )
+                        .unwrap();
+                    } else {
+                        writeln!(
+                            checks,
+                            "        if let Some(item) = self.{name}.as_ref() {{\n            item.validate_at(&field_path(path, {name:?}))?;\n        }}"
+                        )
+                        .unwrap();
+                    }
+                    continue;
+                }
+                if constraints.min_value.is_none() && constraints.max_value.is_none() {
+                    continue;
+                }
+                let as_f64 = match field.type_() {
+                    Type::TYPE_DOUBLE => "value",
+                    Type::TYPE_FLOAT
+                    | Type::TYPE_INT32
+                    | Type::TYPE_SINT32
+                    | Type::TYPE_SFIXED32
+                    | Type::TYPE_UINT32
+                    | Type::TYPE_FIXED32 => "f64::from(value)",
+                    Type::TYPE_INT64
+                    | Type::TYPE_SINT64
+                    | Type::TYPE_SFIXED64
+                    | Type::TYPE_UINT64
+                    | Type::TYPE_FIXED64 => "value as f64",
+                    other => {
+                        return Err(format!(
+                            "{full_name}.{name}: bounds are not supported for {other:?}"
+                        ))
+                    }
+                };
+                let check = format!(
+                    "check_bounds(&field_path(path, {name:?}), {as_f64}, {}, {}, {})?;",
+                    option_literal(&constraints.min_value),
+                    option_literal(&constraints.max_value),
+                    option_literal(&constraints.unit.as_deref())
+                );
+                if repeated {
+                    writeln!(
+                        checks,
+                        "        for value in self.{name}.iter().copied() {{\n            {check}\n        }}"
+                    )
+                } else if field.proto3_optional() {
+                    writeln!(
+                        checks,
+                        "        if let Some(value) = self.{name} {{\n            {check}\n        }}"
+                    )
+                } else {
+                    writeln!(
+                        checks,
+                        "        let value = self.{name};\n        {check}"
+                    )
+                }
+                .unwrap();
+            }
+
+            writeln!(
+                impls,
+                "impl {rust_type} {{
+    /// Checks the fields against the bounds declared in the protobuf definition.
+    /// `(readonly)` and `(writeonly)` are not enforced.
+    pub fn validate(&self) -> Result<(), ValidationError> {{
+        self.validate_at(\"\")
+    }}
+
+    #[allow(unused_variables)]
+    pub(crate) fn validate_at(&self, path: &str) -> Result<(), ValidationError> {{
+{checks}        Ok(())
+    }}
+}}
+",
+                rust_type = rust_types[&full_name]
+            )
+            .unwrap();
+        }
+    }
+
+    Ok(format!(
+        "// @generated by build.rs from the options of uservices_options.proto
+
+/// The constraints declared for the fields of the messages of {packages}.
+///
+/// `(readonly)` and `(writeonly)` are listed, but not enforced by `validate()`, as
+/// a message does not know whether it is sent by the client or by the service.
+pub const FIELD_CONSTRAINTS: &[FieldConstraint] = &[
+{table}];
+
+{impls}"
+    ))
+}
+
+// Converts a CamelCase name of the protobuf definition to snake_case.
+fn snake_case(name: &str) -> String {
+    let mut snake_case = String::new();
+    for (index, c) in name.chars().enumerate() {
+        if c.is_uppercase() && index > 0 {
+            snake_case.push('_');
+        }
+        snake_case.push(c.to_ascii_lowercase());
+    }
+    snake_case
+}
+
+// Resolves a relative message name in the scope of a package following the protobuf
+// name resolution rules, i.e. from the innermost to the outermost scope.
+fn resolve_message(files: &[FileDescriptorProto], package: &str, name: &str) -> Option<String> {
+    if let Some(full_name) = name.strip_prefix('.') {
+        return Some(full_name.to_string());
+    }
+    let messages: Vec<String> = files
+        .iter()
+        .flat_map(|file| {
+            file.message_type
+                .iter()
+                .map(|message| format!("{}.{}", file.package(), message.name()))
+        })
+        .collect();
+    let mut scope = package.to_string();
+    loop {
+        let candidate = if scope.is_empty() {
+            name.to_string()
+        } else {
+            format!("{scope}.{name}")
+        };
+        if messages.contains(&candidate) {
+            return Some(candidate);
+        }
+        if scope.is_empty() {
+            return None;
+        }
+        scope = scope
+            .rsplit_once('.')
+            .map(|(outer, _)| outer.to_string())
+            .unwrap_or_default();
+    }
+}
+
+// Decodes a uprotocol.UServiceTopic given as publish_topic option into its id, name and message.
+fn decode_topic(bytes: &[u8]) -> Result<(u32, String, String), String> {
+    let mut input = CodedInputStream::from_bytes(bytes);
+    let (mut id, mut name, mut message) = (0, String::new(), String::new());
+    while let Some(tag) = input.read_raw_tag_or_eof().map_err(|e| e.to_string())? {
+        match tag >> 3 {
+            1 => id = input.read_uint32().map_err(|e| e.to_string())?,
+            2 => name = input.read_string().map_err(|e| e.to_string())?,
+            3 => message = input.read_string().map_err(|e| e.to_string())?,
+            field => return Err(format!("unexpected field {field} in publish_topic")),
+        }
+    }
+    Ok((id, name, message))
+}
+
+// Generates a module with the uProtocol metadata and UUri builders for every
+// service in the service files, e.g. `descriptors::horn` for the service `Horn`.
+fn generate_descriptors(
+    files: &[FileDescriptorProto],
+    service_files: &[&str],
+) -> Result<String, String> {
+    let mut modules = String::new();
+    for file in files
+        .iter()
+        .filter(|file| service_files.contains(&file.name()))
+    {
+        for service in &file.service {
+            let options = service.options.special_fields.unknown_fields();
+            let varint = |number| match options.get(number) {
+                Some(UnknownValueRef::Varint(value)) => Ok(value),
+                _ => Err(format!(
+                    "service {}: option {number} is missing",
+                    service.name()
+                )),
+            };
+            let id = u32::try_from(varint(SERVICE_ID)?).map_err(|e| e.to_string())?;
+            let version_major =
+                u8::try_from(varint(SERVICE_VERSION_MAJOR)?).map_err(|e| e.to_string())?;
+            let version_minor = varint(SERVICE_VERSION_MINOR).unwrap_or(0);
+            let name = match options.get(SERVICE_NAME) {
+                Some(UnknownValueRef::LengthDelimited(bytes)) => {
+                    String::from_utf8(bytes.to_vec()).map_err(|e| e.to_string())?
+                }
+                _ => {
+                    return Err(format!(
+                        "service {}: service_name is missing",
+                        service.name()
+                    ))
+                }
+            };
+
+            let mut constants = String::new();
+            let mut builders = String::new();
+            let mut methods = String::new();
+            for method in &service.method {
+                let method_id = match method
+                    .options
+                    .special_fields
+                    .unknown_fields()
+                    .get(METHOD_ID)
+                {
+                    Some(UnknownValueRef::Varint(value)) => {
+                        u16::try_from(value).map_err(|e| e.to_string())?
+                    }
+                    _ => return Err(format!("method {}: method_id is missing", method.name())),
+                };
+                let snake_case = snake_case(method.name());
+                writeln!(
+                    constants,
+                    "        /// The resource id of the method `{}`.\n        pub const {}: u16 = {method_id:#06x};",
+                    method.name(),
+                    snake_case.to_uppercase()
+                )
+                .unwrap();
+                writeln!(
+                    builders,
+                    "\n    /// The URI of the method `{}` of the service at the given authority.\n    pub fn {snake_case}_uri(authority: &str) -> Result<UUri, UUriError> {{\n        UUri::try_from_parts(authority, SERVICE_ID, VERSION_MAJOR, methods::{})\n    }}",
+                    method.name(),
+                    snake_case.to_uppercase()
+                )
+                .unwrap();
+                writeln!(
+                    methods,
+                    "            MethodDescriptor {{ name: {:?}, id: methods::{}, input: {:?}, output: {:?} }},",
+                    method.name(),
+                    snake_case.to_uppercase(),
+                    &method.input_type()[1..],
+                    &method.output_type()[1..]
+                )
+                .unwrap();
+            }
+
+            let mut topic_constants = String::new();
+            let mut topics = String::new();
+            for (number, value) in options.iter() {
+                let (number, UnknownValueRef::LengthDelimited(bytes)) = (number, value) else {
+                    continue;
+                };
+                if number != PUBLISH_TOPIC {
+                    continue;
+                }
+                let (topic_id, topic_name, message) = decode_topic(bytes)?;
+                let topic_id = u16::try_from(topic_id).map_err(|e| e.to_string())?;
+                let message = resolve_message(files, file.package(), &message)
+                    .ok_or_else(|| format!("topic {topic_name}: unknown message {message}"))?;
+                let snake_case = snake_case(&topic_name);
+                writeln!(
+                    topic_constants,
+                    "        /// The resource id of the topic `{topic_name}` publishing `{message}`.\n        pub const {}: u16 = {topic_id:#06x};",
+                    snake_case.to_uppercase()
+                )
+                .unwrap();
+                writeln!(
+                    builders,
+                    "\n    /// The URI of the topic `{topic_name}` of the service at the given authority.\n    pub fn {snake_case}_topic_uri(authority: &str) -> Result<UUri, UUriError> {{\n        UUri::try_from_parts(authority, SERVICE_ID, VERSION_MAJOR, topics::{})\n    }}",
+                    snake_case.to_uppercase()
+                )
+                .unwrap();
+                writeln!(
+                    topics,
+                    "            TopicDescriptor {{ name: {topic_name:?}, id: topics::{}, message: {message:?} }},",
+                    snake_case.to_uppercase()
+                )
+                .unwrap();
+            }
+
+            writeln!(
+                modules,
+                "/// The uProtocol metadata of the service `{}.{}`.
+pub mod {module} {{
+    use super::*;
+
+    pub const SERVICE_NAME: &str = {name:?};
+    /// The uEntity id of the service.
+    pub const SERVICE_ID: u32 = {id:#06x};
+    pub const VERSION_MAJOR: u8 = {version_major};
+    pub const VERSION_MINOR: u32 = {version_minor};
+
+    pub mod methods {{
+{constants}    }}
+
+    pub mod topics {{
+{topic_constants}    }}
+
+    pub const DESCRIPTOR: ServiceDescriptor = ServiceDescriptor {{
+        name: SERVICE_NAME,
+        id: SERVICE_ID,
+        version_major: VERSION_MAJOR,
+        version_minor: VERSION_MINOR,
+        methods: &[
+{methods}        ],
+        topics: &[
+{topics}        ],
+    }};
+
+    /// The URI of the service entity at the given authority, e.g. to create its transport.
+    pub fn entity_uri(authority: &str) -> Result<UUri, UUriError> {{
+        UUri::try_from_parts(authority, SERVICE_ID, VERSION_MAJOR, 0)
+    }}
+{builders}}}
+",
+                file.package(),
+                service.name(),
+                module = snake_case(service.name()),
+            )
+            .unwrap();
+        }
+    }
+    Ok(format!(
+        "// @generated by build.rs from the options of uprotocol/uoptions.proto
+
+{modules}"
+    ))
+}
+
+// Generates a typed client and a service trait with a registration helper for every
+// service in the service files, e.g. `HornClient`, `HornService` and `register_horn_service`.
+fn generate_rpc(files: &[FileDescriptorProto], service_files: &[&str]) -> Result<String, String> {
+    let validated: Vec<_> = files
+        .iter()
+        .filter(|file| service_files.contains(&file.name()))
+        .collect();
+    let rust_types = rust_types(&validated)?;
+    let rust_type = |name: &str| {
+        rust_types
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("unknown message {name}"))
+    };
+
+    let mut code = String::new();
+    for file in &validated {
+        for service in &file.service {
+            let service_name = service.name();
+            let module = snake_case(service_name);
+            let mut client_fields = String::new();
+            let mut client_uris = String::new();
+            let mut client_methods = String::new();
+            let mut trait_methods = String::new();
+            let mut handlers = String::new();
+            let mut registrations = String::new();
+            for method in &service.method {
+                let method_name = method.name();
+                let snake_case = snake_case(method_name);
+                let input = rust_type(method.input_type())?;
+                let output = rust_type(method.output_type())?;
+                let handler = format!("{service_name}{method_name}Handler");
+
+                writeln!(client_fields, "    {snake_case}_uri: UUri,").unwrap();
+                writeln!(
+                    client_uris,
+                    "            {snake_case}_uri: crate::descriptors::{module}::{snake_case}_uri(authority)?,"
+                )
+                .unwrap();
+                writeln!(
+                    client_methods,
+                    "
+    /// Invokes the method `{method_name}`.
+    pub async fn {snake_case}(&self, request: {input}) -> Result<{output}, RpcError> {{
+        self.invoke(self.{snake_case}_uri.clone(), request).await
+    }}"
+                )
+                .unwrap();
+                writeln!(
+                    trait_methods,
+                    "
+    /// Handles a request of the method `{method_name}`.
+    async fn {snake_case}(&self, request: {input}) -> Result<{output}, ServiceInvocationError>;"
+                )
+                .unwrap();
+                writeln!(
+                    handlers,
+                    "
+struct {handler}(Arc<dyn {service_name}Service>);
+
+#[async_trait::async_trait]
+impl RequestHandler for {handler} {{
+    async fn handle_request(
+        &self,
+        _resource_id: u16,
+        request_payload: Option<UPayload>,
+    ) -> Result<Option<UPayload>, ServiceInvocationError> {{
+        let request: {input} = extract_request(request_payload)?;
+        let response = self.0.{snake_case}(request).await?;
+        wrap_response(response).map(Some)
+    }}
+}}"
+                )
+                .unwrap();
+                writeln!(
+                    registrations,
+                    "    rpc_server
+        .register_endpoint(
+            None,
+            crate::descriptors::{module}::methods::{},
+            Arc::new({handler}(service.clone())),
+        )
+        .await?;",
+                    snake_case.to_uppercase()
+                )
+                .unwrap();
+            }
+
+            writeln!(
+                code,
+                "/// A typed client of the service `{service_name}`.
+pub struct {service_name}Client {{
+    rpc_client: Arc<dyn RpcClient>,
+    ttl: u32,
+{client_fields}}}
+
+impl {service_name}Client {{
+    /// Creates a client invoking the service at the given authority, waiting up to `ttl` milliseconds for each response.
+    pub fn new(rpc_client: Arc<dyn RpcClient>, authority: &str, ttl: u32) -> Result<Self, UUriError> {{
+        Ok(Self {{
+            rpc_client,
+            ttl,
+{client_uris}        }})
+    }}
+
+    async fn invoke<Req: MessageFull, Res: MessageFull>(&self, method: UUri, request: Req) -> Result<Res, RpcError> {{
+        invoke(self.rpc_client.as_ref(), method, self.ttl, request).await
+    }}
+{client_methods}}}
+
+/// The methods of the service `{service_name}`, to be registered with `register_{module}_service`.
+#[async_trait::async_trait]
+pub trait {service_name}Service: Send + Sync {{{trait_methods}}}
+{handlers}
+/// Registers the methods of the service `{service_name}` as endpoints of the RPC server.
+pub async fn register_{module}_service(
+    rpc_server: &impl RpcServer,
+    service: Arc<dyn {service_name}Service>,
+) -> Result<(), RegistrationError> {{
+{registrations}    Ok(())
+}}
+"
+            )
+            .unwrap();
+        }
+    }
+    Ok(format!(
+        "// @generated by build.rs from the service definitions
+
+{code}"
+    ))
+}
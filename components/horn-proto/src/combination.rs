@@ -0,0 +1,193 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+//! How an `ActivateHornRequest` is combined with a request the Horn service is still playing.
+//!
+//! The COVESA definition has no field for it, so the mode is carried in the message
+//! `ActivateHornRequestExtension` of horn_extensions.proto, whose fields are merged into
+//! the request. Services which don't know the extension keep its fields as unknown fields
+//! and ignore them. The extension is not part of the JSON representation.
+
+use std::str::FromStr;
+
+use protobuf::{Message, MessageFull};
+
+use crate::horn_extensions::{self, ActivateHornRequestExtension};
+use crate::horn_service::ActivateHornRequest;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CombinationMode {
+    /// The request replaces the running request.
+    #[default]
+    Preempt,
+    /// The request is played after the running and the previously queued requests.
+    Queue,
+    /// The request is rejected while another request is running.
+    IgnoreWhileBusy,
+}
+
+impl CombinationMode {
+    pub const ALL: [CombinationMode; 3] = [
+        CombinationMode::Preempt,
+        CombinationMode::Queue,
+        CombinationMode::IgnoreWhileBusy,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CombinationMode::Preempt => "preempt",
+            CombinationMode::Queue => "queue",
+            CombinationMode::IgnoreWhileBusy => "ignore-while-busy",
+        }
+    }
+
+    /// The mode of the protobuf definition, None for `CM_UNSPECIFIED`.
+    pub fn from_proto(mode: horn_extensions::CombinationMode) -> Option<Self> {
+        match mode {
+            horn_extensions::CombinationMode::CM_UNSPECIFIED => None,
+            horn_extensions::CombinationMode::CM_PREEMPT => Some(CombinationMode::Preempt),
+            horn_extensions::CombinationMode::CM_QUEUE => Some(CombinationMode::Queue),
+            horn_extensions::CombinationMode::CM_IGNORE_WHILE_BUSY => {
+                Some(CombinationMode::IgnoreWhileBusy)
+            }
+        }
+    }
+}
+
+impl From<CombinationMode> for horn_extensions::CombinationMode {
+    fn from(mode: CombinationMode) -> Self {
+        match mode {
+            CombinationMode::Preempt => horn_extensions::CombinationMode::CM_PREEMPT,
+            CombinationMode::Queue => horn_extensions::CombinationMode::CM_QUEUE,
+            CombinationMode::IgnoreWhileBusy => {
+                horn_extensions::CombinationMode::CM_IGNORE_WHILE_BUSY
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for CombinationMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for CombinationMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown combination mode '{name}', expected one of: {}",
+                    Self::ALL.map(|mode| mode.name()).join(", ")
+                )
+            })
+    }
+}
+
+// Encoding a proto3 message only fails for messages above 2 GiB, which the requests
+// are far from, so that the encodings below are infallible in practice.
+impl ActivateHornRequest {
+    /// The extension merged into the request, empty if the caller didn't set one.
+    pub fn extension(&self) -> ActivateHornRequestExtension {
+        // The fields of the request are unknown fields of the extension
+        let mut extension = ActivateHornRequestExtension::new();
+        if let Ok(bytes) = self.write_to_bytes() {
+            if extension.merge_from_bytes(&bytes).is_err() {
+                return ActivateHornRequestExtension::new();
+            }
+        }
+        extension.special_fields = Default::default();
+        extension
+    }
+
+    /// Replaces the extension merged into the request.
+    pub fn set_extension(&mut self, extension: &ActivateHornRequestExtension) {
+        let unknown_fields = self.special_fields.mut_unknown_fields();
+        for field in ActivateHornRequestExtension::descriptor().fields() {
+            unknown_fields.remove(field.number() as u32);
+        }
+        if let Ok(bytes) = extension.write_to_bytes() {
+            // The fields of the extension end up as unknown fields of the request
+            let _ = self.merge_from_bytes(&bytes);
+        }
+    }
+
+    /// The combination mode requested by the caller, if any.
+    pub fn combination_mode(&self) -> Option<CombinationMode> {
+        self.extension()
+            .combination_mode
+            .enum_value()
+            .ok()
+            .and_then(CombinationMode::from_proto)
+    }
+
+    pub fn set_combination_mode(&mut self, mode: CombinationMode) {
+        let mut extension = self.extension();
+        extension.combination_mode = horn_extensions::CombinationMode::from(mode).into();
+        self.set_extension(&extension);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern;
+
+    #[test]
+    fn combination_modes_survive_the_encoding() {
+        for mode in CombinationMode::ALL {
+            let mut request = pattern::parse_request("100/100").unwrap();
+            request.set_combination_mode(mode);
+            let bytes = request.write_to_bytes().unwrap();
+            let decoded = ActivateHornRequest::parse_from_bytes(&bytes).unwrap();
+            assert_eq!(decoded.combination_mode(), Some(mode));
+            assert_eq!(decoded.command, request.command);
+        }
+    }
+
+    #[test]
+    fn requests_without_extension_have_no_mode() {
+        let request = pattern::parse_request("100/100").unwrap();
+        assert_eq!(request.combination_mode(), None);
+        assert_eq!(request.extension(), ActivateHornRequestExtension::new());
+    }
+
+    #[test]
+    fn the_extension_is_replaced() {
+        let mut request = pattern::parse_request("continuous").unwrap();
+        request.set_combination_mode(CombinationMode::Queue);
+        request.set_combination_mode(CombinationMode::IgnoreWhileBusy);
+        assert_eq!(
+            request.combination_mode(),
+            Some(CombinationMode::IgnoreWhileBusy)
+        );
+        assert_eq!(request.special_fields.unknown_fields().iter().count(), 1);
+
+        // the extension is the encoding of its own message appended to the request
+        let mut bytes = pattern::parse_request("continuous")
+            .unwrap()
+            .write_to_bytes()
+            .unwrap();
+        let extension = ActivateHornRequestExtension {
+            combination_mode: horn_extensions::CombinationMode::CM_QUEUE.into(),
+            ..Default::default()
+        };
+        bytes.extend(extension.write_to_bytes().unwrap());
+        let request = ActivateHornRequest::parse_from_bytes(&bytes).unwrap();
+        assert_eq!(request.combination_mode(), Some(CombinationMode::Queue));
+    }
+}
@@ -0,0 +1,100 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+//! The uProtocol metadata of the services, generated by the build script from the
+//! `uprotocol.*` options in the protobuf definitions, e.g. for the Horn service:
+//!
+//! ```ignore
+//! use horn_proto::descriptors::horn;
+//!
+//! let uri = horn::activate_horn_uri("horn-service")?;
+//! rpc_server.register_endpoint(None, horn::methods::ACTIVATE_HORN, handler).await?;
+//! ```
+
+#[cfg(uservices)]
+use up_rust::{UUri, UUriError};
+
+/// A service with its methods and topics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ServiceDescriptor {
+    pub name: &'static str,
+    pub id: u32,
+    pub version_major: u8,
+    pub version_minor: u32,
+    pub methods: &'static [MethodDescriptor],
+    pub topics: &'static [TopicDescriptor],
+}
+
+/// A method of a service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MethodDescriptor {
+    pub name: &'static str,
+    /// The resource id of the method.
+    pub id: u16,
+    /// The full name of the request message.
+    pub input: &'static str,
+    /// The full name of the response message.
+    pub output: &'static str,
+}
+
+/// A topic published by a service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TopicDescriptor {
+    pub name: &'static str,
+    /// The resource id of the topic.
+    pub id: u16,
+    /// The full name of the published message.
+    pub message: &'static str,
+}
+
+include!(concat!(env!("OUT_DIR"), "/descriptors.rs"));
+
+#[cfg(all(test, feature = "horn"))]
+mod tests {
+    use super::horn;
+    use crate::horn_service::{ActivateHornRequest, ActivateHornResponse};
+    use crate::horn_topics::HornStatus;
+    use protobuf::MessageFull;
+
+    #[test]
+    fn uris_carry_the_ids_of_the_proto_options() {
+        // service_id 28, service_version_major 1 and the method_ids of horn_service.proto
+        let uri = horn::activate_horn_uri("vehicle").unwrap();
+        assert_eq!(uri.authority_name, "vehicle");
+        assert_eq!(uri.ue_id, 28);
+        assert_eq!(uri.ue_version_major, 1);
+        assert_eq!(uri.resource_id, 1);
+        assert_eq!(horn::deactivate_horn_uri("vehicle").unwrap().resource_id, 2);
+        assert_eq!(horn::horn_topic_uri("vehicle").unwrap().resource_id, 0x8000);
+        assert_eq!(horn::entity_uri("vehicle").unwrap().resource_id, 0);
+    }
+
+    #[test]
+    fn descriptors_name_the_generated_messages() {
+        let descriptor = horn::DESCRIPTOR;
+        assert_eq!(descriptor.name, "body.horn");
+        assert_eq!((descriptor.version_major, descriptor.version_minor), (1, 1));
+        let method = descriptor.methods[0];
+        assert_eq!(method.name, "ActivateHorn");
+        assert_eq!(method.id, horn::methods::ACTIVATE_HORN);
+        assert_eq!(method.input, ActivateHornRequest::descriptor().full_name());
+        assert_eq!(
+            method.output,
+            ActivateHornResponse::descriptor().full_name()
+        );
+        assert_eq!(
+            descriptor.topics[0].message,
+            HornStatus::descriptor().full_name()
+        );
+    }
+}
@@ -0,0 +1,163 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+//! The JSON representation of the Horn service types according to the
+//! [protobuf JSON mapping](https://protobuf.dev/programming-guides/proto3/#json).
+//!
+//! Fields with default values are always included, so that e.g. an inactive horn is
+//! represented with `"isActive": false`. Parsing accepts both the JSON names and the
+//! original field names, and enum values as names or numbers.
+//!
+//! The module can be used with serde for fields of protobuf message types:
+//!
+//! ```ignore
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct AuditEntry {
+//!     #[serde(with = "horn_proto::json")]
+//!     request: ActivateHornRequest,
+//! }
+//! ```
+
+use protobuf::MessageFull;
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub use protobuf_json_mapping::{ParseError, PrintError};
+
+fn print_options() -> protobuf_json_mapping::PrintOptions {
+    protobuf_json_mapping::PrintOptions {
+        always_output_default_values: true,
+        ..Default::default()
+    }
+}
+
+/// Prints a message as JSON string.
+pub fn to_string<M: MessageFull>(message: &M) -> Result<String, PrintError> {
+    protobuf_json_mapping::print_to_string_with_options(message, &print_options())
+}
+
+/// Converts a message to a JSON value, e.g. to embed it in another JSON document.
+pub fn to_value<M: MessageFull>(
+    message: &M,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_str(&to_string(message)?)?)
+}
+
+/// Parses a message from a JSON string.
+pub fn from_str<M: MessageFull>(json: &str) -> Result<M, ParseError> {
+    protobuf_json_mapping::parse_from_str(json)
+}
+
+/// Parses a message from a JSON value, e.g. taken from another JSON or YAML document.
+pub fn from_value<M: MessageFull>(json: &serde_json::Value) -> Result<M, ParseError> {
+    from_str(&json.to_string())
+}
+
+/// Serializes a message in its JSON representation, for use with `#[serde(with = "horn_proto::json")]`.
+pub fn serialize<M: MessageFull, S: Serializer>(
+    message: &M,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    to_value(message)
+        .map_err(|e| S::Error::custom(e.to_string()))?
+        .serialize(serializer)
+}
+
+/// Deserializes a message from its JSON representation, for use with `#[serde(with = "horn_proto::json")]`.
+pub fn deserialize<'de, M: MessageFull, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<M, D::Error> {
+    let json = serde_json::Value::deserialize(deserializer)?;
+    from_value(&json).map_err(D::Error::custom)
+}
+
+#[cfg(all(test, feature = "horn"))]
+mod tests {
+    use super::*;
+    use crate::horn_service::ActivateHornRequest;
+    use crate::horn_topics::{HornMode, HornStatus};
+    use crate::pattern;
+    use serde_json::json;
+
+    #[test]
+    fn requests_round_trip_through_json() {
+        let request = pattern::parse_request("2x(100/50) 300/300; 60/40").unwrap();
+        let json = to_string(&request).unwrap();
+        assert_eq!(from_str::<ActivateHornRequest>(&json).unwrap(), request);
+
+        let value = to_value(&request).unwrap();
+        assert_eq!(value["mode"], "HM_SEQUENCED");
+        assert_eq!(value["command"][0]["hornCycles"][2]["onTime"], 300);
+        assert_eq!(from_value::<ActivateHornRequest>(&value).unwrap(), request);
+    }
+
+    #[test]
+    fn default_values_are_always_output() {
+        assert_eq!(
+            to_value(&HornStatus::new()).unwrap(),
+            json!({
+                "isActive": false,
+                "mode": "HM_UNSPECIFIED",
+                "isFaultActive": false,
+                "priority": 0,
+            })
+        );
+        assert_eq!(
+            to_value(&ActivateHornRequest::new()).unwrap(),
+            json!({ "mode": "HM_UNSPECIFIED", "command": [] })
+        );
+    }
+
+    #[test]
+    fn parsing_accepts_field_names_and_enum_numbers() {
+        let status: HornStatus =
+            from_str(r#"{"is_active": true, "mode": 3, "currentSequence": 2}"#).unwrap();
+        assert!(status.is_active);
+        assert_eq!(status.mode.enum_value(), Ok(HornMode::HM_CONTINUOUS));
+        assert_eq!(status.current_sequence, Some(2));
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        assert!(from_str::<HornStatus>(r#"{"isActive": true, "volume": 11}"#).is_err());
+        assert!(from_value::<HornStatus>(&json!({"mode": "HM_LOUD"})).is_err());
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct AuditEntry {
+        caller: String,
+        #[serde(with = "crate::json")]
+        request: ActivateHornRequest,
+    }
+
+    #[test]
+    fn messages_are_embedded_with_serde() {
+        let entry = AuditEntry {
+            caller: "horn-client".to_string(),
+            request: pattern::parse_request("continuous").unwrap(),
+        };
+        let value = serde_json::to_value(&entry).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "caller": "horn-client",
+                "request": { "mode": "HM_CONTINUOUS", "command": [] },
+            })
+        );
+        assert_eq!(serde_json::from_value::<AuditEntry>(value).unwrap(), entry);
+
+        let invalid = json!({ "caller": "horn-client", "request": { "mode": true } });
+        assert!(serde_json::from_value::<AuditEntry>(invalid).is_err());
+    }
+}
@@ -12,3 +12,15 @@
 *******************************************************************************/
 
 include!(concat!(env!("OUT_DIR"), "/uservice/mod.rs"));
+
+#[cfg(feature = "horn")]
+pub mod combination;
+pub mod descriptors;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "horn")]
+pub mod pattern;
+// Typed clients and services only exist for enabled services, see build.rs
+#[cfg(uservices)]
+pub mod rpc;
+pub mod validate;
@@ -0,0 +1,491 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+//! Builders and a compact notation for horn patterns.
+//!
+//! A sequence is written as horn cycles of on and off times in milliseconds, separated
+//! by whitespace or commas. Cycles and groups of cycles can be repeated with `Nx`:
+//!
+//! ```text
+//! 100/100,200/300          two cycles
+//! 3x(100/100) 500/30       three short honks followed by a long one
+//! 2x(3x(60/40) 300/300)    nested groups
+//! ```
+//!
+//! A request consists of the sequences separated by `;`, or of the word `continuous`.
+//! Formatting collapses runs of equal cycles, so that `parse_sequence(&format_sequence(s))`
+//! returns `s` for non-negative times, while the text may differ from the originally parsed one.
+//! A sequence without cycles, which the Horn service rejects, is written as the word `empty`.
+
+use std::time::Duration;
+
+use crate::horn_service::ActivateHornRequest;
+use crate::horn_topics::{HornCycle, HornMode, HornSequence};
+
+/// The maximum number of cycles a sequence in the notation may expand to.
+pub const MAX_CYCLES: usize = 10_000;
+
+/// The maximum nesting depth of groups in the notation.
+pub const MAX_DEPTH: usize = 16;
+
+const CONTINUOUS: &str = "continuous";
+const EMPTY: &str = "empty";
+
+/// Builds a `HornSequence` from cycles and repetitions.
+#[derive(Clone, Debug, Default)]
+pub struct SequenceBuilder {
+    horn_cycles: Vec<HornCycle>,
+}
+
+impl SequenceBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a cycle with the given on and off times in milliseconds.
+    pub fn cycle(mut self, on_time: i32, off_time: i32) -> Self {
+        self.horn_cycles.push(HornCycle {
+            on_time,
+            off_time,
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Appends `count` equal cycles.
+    pub fn repeat(self, count: usize, on_time: i32, off_time: i32) -> Self {
+        (0..count).fold(self, |builder, _| builder.cycle(on_time, off_time))
+    }
+
+    /// Appends `count` repetitions of the cycles of another sequence.
+    pub fn repeat_sequence(mut self, count: usize, sequence: impl Into<HornSequence>) -> Self {
+        let sequence = sequence.into();
+        for _ in 0..count {
+            self.horn_cycles
+                .extend(sequence.horn_cycles.iter().cloned());
+        }
+        self
+    }
+
+    pub fn build(self) -> HornSequence {
+        HornSequence {
+            horn_cycles: self.horn_cycles,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<SequenceBuilder> for HornSequence {
+    fn from(builder: SequenceBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Builds an `ActivateHornRequest`.
+#[derive(Clone, Debug)]
+pub struct RequestBuilder {
+    request: ActivateHornRequest,
+}
+
+impl RequestBuilder {
+    /// A request playing the added sequences one after the other.
+    pub fn sequenced() -> Self {
+        Self {
+            request: ActivateHornRequest {
+                mode: HornMode::HM_SEQUENCED.into(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// A request activating the horn until it is deactivated.
+    pub fn continuous() -> Self {
+        Self {
+            request: ActivateHornRequest {
+                mode: HornMode::HM_CONTINUOUS.into(),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn sequence(mut self, sequence: impl Into<HornSequence>) -> Self {
+        self.request.command.push(sequence.into());
+        self
+    }
+
+    pub fn build(self) -> ActivateHornRequest {
+        self.request
+    }
+}
+
+impl HornSequence {
+    pub fn builder() -> SequenceBuilder {
+        SequenceBuilder::new()
+    }
+
+    /// The time it takes to play all cycles once. Negative times count as zero.
+    pub fn duration(&self) -> Duration {
+        let millis = self
+            .horn_cycles
+            .iter()
+            .map(|cycle| {
+                u64::try_from(cycle.on_time).unwrap_or(0)
+                    + u64::try_from(cycle.off_time).unwrap_or(0)
+            })
+            .sum();
+        Duration::from_millis(millis)
+    }
+}
+
+impl ActivateHornRequest {
+    /// The time it takes to play all sequences once, zero for a continuous request.
+    pub fn duration(&self) -> Duration {
+        self.command.iter().map(HornSequence::duration).sum()
+    }
+}
+
+/// A text which is not a valid horn pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NotationError {
+    /// The byte offset in the text where the error was detected.
+    pub position: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for NotationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+/// Parses a sequence like `3x(100/100) 500/30`.
+pub fn parse_sequence(text: &str) -> Result<HornSequence, NotationError> {
+    if text.trim() == EMPTY {
+        return Ok(HornSequence::new());
+    }
+    let mut parser = Parser { text, position: 0 };
+    let horn_cycles = parser.cycles(0)?;
+    if horn_cycles.is_empty() {
+        return Err(parser.error("expected at least one horn cycle ON/OFF".into()));
+    }
+    Ok(HornSequence {
+        horn_cycles,
+        ..Default::default()
+    })
+}
+
+/// Parses a request like `3x(100/100) 500/30; 200/200` or `continuous`.
+pub fn parse_request(text: &str) -> Result<ActivateHornRequest, NotationError> {
+    if text.trim() == CONTINUOUS {
+        return Ok(RequestBuilder::continuous().build());
+    }
+    let mut builder = RequestBuilder::sequenced();
+    let mut offset = 0;
+    for sequence in text.split(';') {
+        builder = builder.sequence(parse_sequence(sequence).map_err(|e| NotationError {
+            position: offset + e.position,
+            ..e
+        })?);
+        offset += sequence.len() + 1;
+    }
+    Ok(builder.build())
+}
+
+/// Formats a sequence in the compact notation, e.g. `3x(100/100) 500/30`.
+pub fn format_sequence(sequence: &HornSequence) -> String {
+    if sequence.horn_cycles.is_empty() {
+        return EMPTY.to_string();
+    }
+    let mut items = Vec::new();
+    let mut cycles = sequence.horn_cycles.iter().peekable();
+    while let Some(cycle) = cycles.next() {
+        let mut count = 1;
+        while cycles.next_if(|next| *next == cycle).is_some() {
+            count += 1;
+        }
+        let cycle = format!("{}/{}", cycle.on_time, cycle.off_time);
+        items.push(match count {
+            1 => cycle,
+            _ => format!("{count}x({cycle})"),
+        });
+    }
+    items.join(" ")
+}
+
+/// Formats a request in the compact notation, e.g. `3x(100/100) 500/30; 200/200`.
+pub fn format_request(request: &ActivateHornRequest) -> String {
+    if request.mode.enum_value() == Ok(HornMode::HM_CONTINUOUS) {
+        return CONTINUOUS.to_string();
+    }
+    request
+        .command
+        .iter()
+        .map(format_sequence)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn error(&self, message: String) -> NotationError {
+        NotationError {
+            position: self.position,
+            message,
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace() || *c == ',') {
+            self.position += c.len_utf8();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), NotationError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.position += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("expected '{expected}' but found '{c}'"))),
+            None => Err(self.error(format!("expected '{expected}'"))),
+        }
+    }
+
+    fn number(&mut self) -> Result<u32, NotationError> {
+        let start = self.position;
+        let digits = self.text[start..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.text.len() - start);
+        if digits == 0 {
+            return Err(self.error("expected a number".into()));
+        }
+        self.position += digits;
+        self.text[start..self.position]
+            .parse()
+            .map_err(|e| NotationError {
+                position: start,
+                message: format!("invalid number: {e}"),
+            })
+    }
+
+    // Parses items up to the end of the text or a closing parenthesis at the given depth.
+    fn cycles(&mut self, depth: usize) -> Result<Vec<HornCycle>, NotationError> {
+        let mut cycles = Vec::new();
+        loop {
+            self.skip_separators();
+            match self.peek() {
+                None => return Ok(cycles),
+                Some(')') if depth > 0 => return Ok(cycles),
+                Some(c) if c.is_ascii_digit() => {}
+                Some(c) => return Err(self.error(format!("unexpected '{c}'"))),
+            }
+            let start = self.position;
+            let number = self.number()?;
+            if self.peek() == Some('x') {
+                self.position += 1;
+                if number == 0 {
+                    return Err(NotationError {
+                        position: start,
+                        message: "the repetition count must be positive".into(),
+                    });
+                }
+                let group = if self.peek() == Some('(') {
+                    if depth == MAX_DEPTH {
+                        return Err(self.error(format!(
+                            "the groups are nested deeper than {MAX_DEPTH} levels"
+                        )));
+                    }
+                    self.position += 1;
+                    let group = self.cycles(depth + 1)?;
+                    self.expect(')')?;
+                    group
+                } else {
+                    let on_time = self.number()?;
+                    vec![self.cycle(on_time, start)?]
+                };
+                if group.len().saturating_mul(number as usize) > MAX_CYCLES - cycles.len() {
+                    return Err(NotationError {
+                        position: start,
+                        message: format!("the sequence expands to more than {MAX_CYCLES} cycles"),
+                    });
+                }
+                for _ in 0..number {
+                    cycles.extend(group.iter().cloned());
+                }
+            } else {
+                if cycles.len() == MAX_CYCLES {
+                    return Err(NotationError {
+                        position: start,
+                        message: format!("the sequence expands to more than {MAX_CYCLES} cycles"),
+                    });
+                }
+                cycles.push(self.cycle(number, start)?);
+            }
+        }
+    }
+
+    // Parses the remainder `/OFF` of a cycle whose on time has been read.
+    fn cycle(&mut self, on_time: u32, start: usize) -> Result<HornCycle, NotationError> {
+        self.expect('/')?;
+        let off_time = self.number()?;
+        let time = |time: u32| {
+            i32::try_from(time).map_err(|_| NotationError {
+                position: start,
+                message: format!("the time {time} ms is too large"),
+            })
+        };
+        Ok(HornCycle {
+            on_time: time(on_time)?,
+            off_time: time(off_time)?,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times(sequence: &HornSequence) -> Vec<(i32, i32)> {
+        sequence
+            .horn_cycles
+            .iter()
+            .map(|cycle| (cycle.on_time, cycle.off_time))
+            .collect()
+    }
+
+    #[test]
+    fn cycles_and_groups_are_expanded() {
+        let sequence = parse_sequence("3x(100/100) 500/30").unwrap();
+        assert_eq!(
+            times(&sequence),
+            [(100, 100), (100, 100), (100, 100), (500, 30)]
+        );
+        assert_eq!(sequence.duration(), Duration::from_millis(1130));
+        assert_eq!(
+            times(&parse_sequence(" 100/100,200/300 ").unwrap()),
+            [(100, 100), (200, 300)]
+        );
+        assert_eq!(
+            times(&parse_sequence("2x(2x60/40 300/300)").unwrap()),
+            [
+                (60, 40),
+                (60, 40),
+                (300, 300),
+                (60, 40),
+                (60, 40),
+                (300, 300)
+            ]
+        );
+    }
+
+    #[test]
+    fn requests_are_split_into_sequences() {
+        let request = parse_request("2x(60/40); 3x(200/200)").unwrap();
+        assert_eq!(request.mode.enum_value(), Ok(HornMode::HM_SEQUENCED));
+        assert_eq!(request.command.len(), 2);
+        assert_eq!(times(&request.command[1]), [(200, 200); 3]);
+        assert_eq!(request.duration(), Duration::from_millis(1400));
+
+        let request = parse_request(" continuous ").unwrap();
+        assert_eq!(request.mode.enum_value(), Ok(HornMode::HM_CONTINUOUS));
+        assert!(request.command.is_empty());
+    }
+
+    #[test]
+    fn formatting_collapses_equal_cycles() {
+        let sequence = HornSequence::builder()
+            .repeat(3, 100, 100)
+            .cycle(500, 30)
+            .build();
+        assert_eq!(format_sequence(&sequence), "3x(100/100) 500/30");
+        let request = RequestBuilder::sequenced()
+            .sequence(sequence)
+            .sequence(HornSequence::builder().cycle(200, 200))
+            .build();
+        assert_eq!(format_request(&request), "3x(100/100) 500/30; 200/200");
+        assert_eq!(
+            format_request(&RequestBuilder::continuous().build()),
+            "continuous"
+        );
+    }
+
+    #[test]
+    fn formatted_requests_parse_to_the_same_request() {
+        for text in [
+            "3x(100/100) 500/30",
+            "2x(3x(60/40) 300/300)",
+            "30/30; 1000/1000, 1000/1000; 2x(2x(50/50) 100/100)",
+            "continuous",
+        ] {
+            let request = parse_request(text).unwrap();
+            assert_eq!(
+                parse_request(&format_request(&request)).unwrap(),
+                request,
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn empty_sequences_are_formatted_explicitly() {
+        let empty = HornSequence::new();
+        assert_eq!(format_sequence(&empty), "empty");
+        assert_eq!(parse_sequence(&format_sequence(&empty)).unwrap(), empty);
+
+        let request = RequestBuilder::sequenced()
+            .sequence(SequenceBuilder::new().cycle(100, 100).build())
+            .sequence(empty)
+            .build();
+        assert_eq!(format_request(&request), "100/100; empty");
+        assert_eq!(parse_request(&format_request(&request)).unwrap(), request);
+        assert!(parse_sequence("").is_err());
+    }
+
+    #[test]
+    fn invalid_notations_report_the_position() {
+        let error = |text| parse_request(text).unwrap_err();
+        assert_eq!(error("100/100; 200").position, 12);
+        assert_eq!(error("100/100 x").message, "unexpected 'x'");
+        assert_eq!(error("0x(100/100)").position, 0);
+        assert_eq!(error("2x(100/100").message, "expected ')'");
+        assert_eq!(
+            error("100/100;").message,
+            "expected at least one horn cycle ON/OFF"
+        );
+        assert_eq!(error("3000000000/100").position, 0);
+    }
+
+    #[test]
+    fn expansion_and_nesting_are_limited() {
+        assert!(parse_sequence(&format!("{MAX_CYCLES}x(1/1)")).is_ok());
+        let error = parse_sequence(&format!("{MAX_CYCLES}x(1/1) 1/1")).unwrap_err();
+        assert_eq!(error.position, 12);
+
+        let nested = |depth| format!("{}1/1{}", "1x(".repeat(depth), ")".repeat(depth));
+        assert!(parse_sequence(&nested(MAX_DEPTH)).is_ok());
+        let error = parse_sequence(&nested(MAX_DEPTH + 1)).unwrap_err();
+        assert_eq!(error.position, 3 * MAX_DEPTH + 2);
+        assert!(parse_sequence(&"1x(".repeat(100_000)).is_err());
+    }
+}
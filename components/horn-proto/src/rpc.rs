@@ -0,0 +1,188 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+//! Typed RPC clients and service traits, generated by the build script from the service
+//! definitions, e.g. for the Horn service:
+//!
+//! ```ignore
+//! use horn_proto::rpc::{register_horn_service, HornClient};
+//!
+//! let client = HornClient::new(rpc_client, "horn-service", 1_000)?;
+//! let response = client.activate_horn(request).await?;
+//!
+//! register_horn_service(&rpc_server, Arc::new(my_horn_service)).await?;
+//! ```
+//!
+//! Requests without payload are handed to the service as default message, requests which
+//! can't be decoded are rejected with `INVALID_ARGUMENT`.
+
+use std::sync::Arc;
+
+use protobuf::MessageFull;
+use up_rust::communication::{
+    CallOptions, RegistrationError, RequestHandler, RpcClient, RpcServer, ServiceInvocationError,
+    UPayload,
+};
+use up_rust::{UMessageError, UUri, UUriError};
+
+/// The failure of a typed RPC invocation.
+#[derive(Debug)]
+pub enum RpcError {
+    /// The invocation failed or the service replied with an error status.
+    Invocation(ServiceInvocationError),
+    /// The service replied without payload.
+    EmptyResponse,
+    /// The request could not be encoded or the response could not be decoded.
+    Payload(UMessageError),
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RpcError::Invocation(e) => write!(f, "invocation failed: {e}"),
+            RpcError::EmptyResponse => write!(f, "response without payload"),
+            RpcError::Payload(e) => write!(f, "invalid payload: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+impl From<ServiceInvocationError> for RpcError {
+    fn from(e: ServiceInvocationError) -> Self {
+        RpcError::Invocation(e)
+    }
+}
+
+impl From<UMessageError> for RpcError {
+    fn from(e: UMessageError) -> Self {
+        RpcError::Payload(e)
+    }
+}
+
+async fn invoke<Req: MessageFull, Res: MessageFull>(
+    rpc_client: &dyn RpcClient,
+    method: UUri,
+    ttl: u32,
+    request: Req,
+) -> Result<Res, RpcError> {
+    let payload = UPayload::try_from_protobuf(request)?;
+    let call_options = CallOptions::for_rpc_request(ttl, None, None, None);
+    match rpc_client
+        .invoke_method(method, call_options, Some(payload))
+        .await?
+    {
+        Some(payload) => Ok(payload.extract_protobuf()?),
+        None => Err(RpcError::EmptyResponse),
+    }
+}
+
+fn extract_request<Req: MessageFull>(
+    request_payload: Option<UPayload>,
+) -> Result<Req, ServiceInvocationError> {
+    match request_payload {
+        Some(payload) => payload.extract_protobuf().map_err(|e| {
+            ServiceInvocationError::InvalidArgument(format!(
+                "invalid {}: {e}",
+                Req::descriptor().name()
+            ))
+        }),
+        None => Ok(Req::default()),
+    }
+}
+
+fn wrap_response<Res: MessageFull>(response: Res) -> Result<UPayload, ServiceInvocationError> {
+    UPayload::try_from_protobuf(response).map_err(|e| {
+        ServiceInvocationError::Internal(format!(
+            "failed to encode {}: {e}",
+            Res::descriptor().name()
+        ))
+    })
+}
+
+include!(concat!(env!("OUT_DIR"), "/rpc.rs"));
+
+#[cfg(all(test, feature = "horn"))]
+mod tests {
+    use super::*;
+    use crate::descriptors::horn;
+    use crate::horn_service::{
+        ActivateHornRequest, ActivateHornResponse, DeactivateHornRequest, DeactivateHornResponse,
+    };
+    use crate::status::Status;
+    use protobuf::MessageField;
+    use up_rust::communication::{InMemoryRpcClient, InMemoryRpcServer};
+    use up_rust::local_transport::LocalTransport;
+
+    // Reports the number of sequences of an activation and refuses deactivations
+    struct CountingService;
+
+    #[async_trait::async_trait]
+    impl HornService for CountingService {
+        async fn activate_horn(
+            &self,
+            request: ActivateHornRequest,
+        ) -> Result<ActivateHornResponse, ServiceInvocationError> {
+            Ok(ActivateHornResponse {
+                status: MessageField::some(Status {
+                    message: format!("{} sequences", request.command.len()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        }
+
+        async fn deactivate_horn(
+            &self,
+            _request: DeactivateHornRequest,
+        ) -> Result<DeactivateHornResponse, ServiceInvocationError> {
+            Err(ServiceInvocationError::FailedPrecondition(
+                "the horn is off".to_string(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn calls_round_trip_through_the_rpc_server() {
+        let transport = Arc::new(LocalTransport::new(
+            "vehicle",
+            horn::SERVICE_ID,
+            horn::VERSION_MAJOR,
+        ));
+        let rpc_server = InMemoryRpcServer::new(transport.clone(), transport.clone());
+        register_horn_service(&rpc_server, Arc::new(CountingService))
+            .await
+            .unwrap();
+        let rpc_client = InMemoryRpcClient::new(transport.clone(), transport)
+            .await
+            .unwrap();
+        let client = HornClient::new(Arc::new(rpc_client), "vehicle", 1_000).unwrap();
+
+        let request = crate::pattern::parse_request("100/100; 200/200").unwrap();
+        let response = client.activate_horn(request).await.unwrap();
+        assert_eq!(response.status.message, "2 sequences");
+
+        let error = client
+            .deactivate_horn(DeactivateHornRequest::new())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                &error,
+                RpcError::Invocation(ServiceInvocationError::FailedPrecondition(message))
+                    if message == "the horn is off"
+            ),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,242 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+//! Validation of the uService messages against the constraints declared with the
+//! field options `(min_value)`, `(max_value)`, `(unit)`, `(readonly)` and `(writeonly)`.
+//!
+//! The `validate()` methods are generated by the build script from the protobuf
+//! definitions. They check the bounds only: `(readonly)` and `(writeonly)` depend on
+//! the direction a message is sent in and are merely listed in `FIELD_CONSTRAINTS`.
+//! Constraints which are only given as comment are checked by hand below.
+
+#[cfg(feature = "horn")]
+use crate::horn_service::ActivateHornRequest;
+
+/// The maximum number of sequences of an `ActivateHornRequest`.
+#[cfg(feature = "horn")]
+pub const MAX_SEQUENCES: usize = 7;
+
+/// The constraints declared for a field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FieldConstraint {
+    /// The full name of the message, e.g. `vehicle.body.horn.v1.HornCycle`.
+    pub message: &'static str,
+    pub field: &'static str,
+    pub min_value: Option<f64>,
+    pub max_value: Option<f64>,
+    pub unit: Option<&'static str>,
+    /// The field is set by the service only.
+    pub readonly: bool,
+    /// The field is set by the client only.
+    pub writeonly: bool,
+}
+
+/// The bound violated by a field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bound {
+    Min(f64),
+    Max(f64),
+    MaxCount(usize),
+}
+
+/// A field which violates its declared constraints.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationError {
+    /// The path of the field in the message, e.g. `command[0].horn_cycles[1].on_time`.
+    pub field: String,
+    pub value: f64,
+    pub bound: Bound,
+    pub unit: Option<&'static str>,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unit = self.unit.map(|unit| format!(" {unit}")).unwrap_or_default();
+        match self.bound {
+            Bound::Min(min) => write!(
+                f,
+                "{}: {}{unit} is below the minimum of {min}{unit}",
+                self.field, self.value
+            ),
+            Bound::Max(max) => write!(
+                f,
+                "{}: {}{unit} is above the maximum of {max}{unit}",
+                self.field, self.value
+            ),
+            Bound::MaxCount(max) => write!(
+                f,
+                "{}: {} elements exceed the maximum of {max}",
+                self.field, self.value
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+#[cfg(uservices)]
+fn field_path(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_string()
+    } else {
+        format!("{path}.{field}")
+    }
+}
+
+#[cfg(uservices)]
+fn check_bounds(
+    field: &str,
+    value: f64,
+    min_value: Option<f64>,
+    max_value: Option<f64>,
+    unit: Option<&'static str>,
+) -> Result<(), ValidationError> {
+    let bound = match (min_value, max_value) {
+        (Some(min), _) if value < min => Bound::Min(min),
+        (_, Some(max)) if value > max => Bound::Max(max),
+        _ => return Ok(()),
+    };
+    Err(ValidationError {
+        field: field.to_string(),
+        value,
+        bound,
+        unit,
+    })
+}
+
+// "For a sequenced request there can be up to 7 sequences", see horn_service.proto
+#[cfg(feature = "horn")]
+fn check_sequence_count(request: &ActivateHornRequest, path: &str) -> Result<(), ValidationError> {
+    if request.command.len() > MAX_SEQUENCES {
+        return Err(ValidationError {
+            field: field_path(path, "command"),
+            value: request.command.len() as f64,
+            bound: Bound::MaxCount(MAX_SEQUENCES),
+            unit: None,
+        });
+    }
+    Ok(())
+}
+
+include!(concat!(env!("OUT_DIR"), "/validate.rs"));
+
+#[cfg(all(test, feature = "horn"))]
+mod tests {
+    use super::*;
+    use crate::horn_topics::{HornCycle, HornSequence, HornStatus};
+
+    fn cycle(on_time: i32, off_time: i32) -> HornCycle {
+        HornCycle {
+            on_time,
+            off_time,
+            ..Default::default()
+        }
+    }
+
+    fn request(sequences: Vec<Vec<HornCycle>>) -> ActivateHornRequest {
+        ActivateHornRequest {
+            command: sequences
+                .into_iter()
+                .map(|horn_cycles| HornSequence {
+                    horn_cycles,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn valid_requests_pass() {
+        assert_eq!(
+            request(vec![vec![cycle(30, 30), cycle(500, 1000)]]).validate(),
+            Ok(())
+        );
+        assert_eq!(
+            request(vec![vec![cycle(100, 100)]; MAX_SEQUENCES]).validate(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn nested_bounds_report_the_field_path() {
+        let error = request(vec![
+            vec![cycle(100, 100)],
+            vec![cycle(100, 100), cycle(20, 100)],
+        ])
+        .validate()
+        .unwrap_err();
+        assert_eq!(error.field, "command[1].horn_cycles[1].on_time");
+        assert_eq!(error.value, 20.0);
+        assert_eq!(error.bound, Bound::Min(30.0));
+        assert_eq!(error.unit, Some("ms"));
+        assert_eq!(
+            error.to_string(),
+            "command[1].horn_cycles[1].on_time: 20 ms is below the minimum of 30 ms"
+        );
+    }
+
+    #[test]
+    fn too_many_sequences_are_rejected() {
+        let error = request(vec![vec![cycle(100, 100)]; MAX_SEQUENCES + 1])
+            .validate()
+            .unwrap_err();
+        assert_eq!(error.field, "command");
+        assert_eq!(error.bound, Bound::MaxCount(MAX_SEQUENCES));
+    }
+
+    #[test]
+    fn optional_fields_are_checked_when_present() {
+        let mut status = HornStatus {
+            priority: 255,
+            ..Default::default()
+        };
+        assert_eq!(status.validate(), Ok(()));
+        status.current_sequence = Some(8);
+        assert_eq!(status.validate().unwrap_err().bound, Bound::Max(7.0));
+    }
+
+    #[test]
+    fn declared_constraints_are_listed() {
+        let on_time = FIELD_CONSTRAINTS
+            .iter()
+            .find(|constraint| {
+                constraint.message == "vehicle.body.horn.v1.HornCycle"
+                    && constraint.field == "on_time"
+            })
+            .unwrap();
+        assert_eq!(on_time.min_value, Some(30.0));
+        assert_eq!(on_time.unit, Some("ms"));
+        assert!(on_time.writeonly && !on_time.readonly);
+    }
+}
+
+#[cfg(all(test, feature = "chime"))]
+mod chime_tests {
+    use crate::chime_service::PlayChimeRequest;
+
+    #[test]
+    fn chime_requests_are_validated() {
+        let mut request = PlayChimeRequest {
+            tone: 3,
+            duration: 500,
+            ..Default::default()
+        };
+        assert_eq!(request.validate(), Ok(()));
+        request.duration = 20_000;
+        assert_eq!(
+            request.validate().unwrap_err().to_string(),
+            "duration: 20000 ms is above the maximum of 10000 ms"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::collections::HashSet;
+
+use horn_proto::status::Status;
+use log::{info, warn};
+use protobuf::Enum;
+use tokio::sync::watch;
+use up_rust::UCode;
+use zenoh::sample::SampleKind;
+
+/// Whether the horn actuator is connected, as announced by its Zenoh liveliness token.
+#[derive(Clone)]
+pub(crate) struct ActuatorPresence {
+    rx_present: watch::Receiver<bool>,
+}
+
+impl ActuatorPresence {
+    /// Assumes the actuator to be always present, if its liveliness is not tracked.
+    pub fn assumed() -> Self {
+        let (_, rx_present) = watch::channel(true);
+        Self { rx_present }
+    }
+
+    /// Tracks the liveliness tokens declared for the key expression of the actuator
+    /// in the Zenoh network of the actuator. The actuator is considered absent
+    /// until a token is declared and after the last token disappeared.
+    pub async fn track(
+        zenoh_config: zenoh::Config,
+        keyexpr: String,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let session = zenoh::open(zenoh_config)
+            .await
+            .map_err(|e| e as Box<dyn std::error::Error>)?;
+        let subscriber = session
+            .liveliness()
+            .declare_subscriber(&keyexpr)
+            .history(true)
+            .await
+            .map_err(|e| e as Box<dyn std::error::Error>)?;
+        info!("Tracking the liveliness of the horn actuator at [{keyexpr}]");
+
+        let (mut tracker, presence) = PresenceTracker::new();
+        tokio::spawn(async move {
+            // Keep the session open as long as the liveliness is tracked
+            let _session = session;
+            while let Ok(sample) = subscriber.recv_async().await {
+                tracker.update(sample.key_expr().to_string(), sample.kind());
+            }
+        });
+        Ok(presence)
+    }
+
+    pub fn is_present(&self) -> bool {
+        *self.rx_present.borrow()
+    }
+
+    /// Waits for the next change of the presence, forever if the presence is assumed.
+    pub async fn changed(&mut self) {
+        if self.rx_present.changed().await.is_err() {
+            std::future::pending().await
+        }
+    }
+}
+
+// Follows the liveliness tokens of the actuator, which is present while any token is declared
+struct PresenceTracker {
+    tokens: HashSet<String>,
+    tx_present: watch::Sender<bool>,
+}
+
+impl PresenceTracker {
+    fn new() -> (Self, ActuatorPresence) {
+        let (tx_present, rx_present) = watch::channel(false);
+        let tracker = Self {
+            tokens: HashSet::new(),
+            tx_present,
+        };
+        (tracker, ActuatorPresence { rx_present })
+    }
+
+    // Applies a liveliness sample, Put for a declared and Delete for an undeclared token
+    fn update(&mut self, token: String, kind: SampleKind) {
+        match kind {
+            SampleKind::Put => {
+                info!("The horn actuator [{token}] is connected");
+                self.tokens.insert(token);
+            }
+            SampleKind::Delete => {
+                warn!("The horn actuator [{token}] disappeared");
+                self.tokens.remove(&token);
+            }
+        }
+        self.tx_present.send_if_modified(|present| {
+            let was_present = std::mem::replace(present, !self.tokens.is_empty());
+            was_present != *present
+        });
+    }
+}
+
+/// The status returned for requests which cannot be executed without the actuator.
+pub(crate) fn absent_status() -> Status {
+    Status {
+        code: UCode::UNAVAILABLE.value(),
+        message: "the horn actuator is not connected".to_string(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const TOKEN: &str = "Vehicle/Body/Horn/IsActive";
+
+    // Whether the presence changed since the last call
+    async fn changed(presence: &mut ActuatorPresence) -> bool {
+        tokio::time::timeout(Duration::from_millis(10), presence.changed())
+            .await
+            .is_ok()
+    }
+
+    #[tokio::test]
+    async fn presence_follows_the_liveliness_tokens() {
+        let (mut tracker, mut presence) = PresenceTracker::new();
+        assert!(!presence.is_present());
+
+        tracker.update(TOKEN.to_string(), SampleKind::Put);
+        assert!(presence.is_present());
+        assert!(changed(&mut presence).await);
+
+        // a second provider of the actuator keeps it present
+        tracker.update(format!("{TOKEN}/2"), SampleKind::Put);
+        tracker.update(TOKEN.to_string(), SampleKind::Delete);
+        assert!(presence.is_present());
+        assert!(!changed(&mut presence).await);
+
+        tracker.update(format!("{TOKEN}/2"), SampleKind::Delete);
+        assert!(!presence.is_present());
+        assert!(changed(&mut presence).await);
+        // deleting an unknown token changes nothing
+        tracker.update(TOKEN.to_string(), SampleKind::Delete);
+        assert!(!changed(&mut presence).await);
+    }
+
+    #[tokio::test]
+    async fn assumed_presence_never_changes() {
+        let mut presence = ActuatorPresence::assumed();
+        assert!(presence.is_present());
+        assert!(!changed(&mut presence).await);
+    }
+}
@@ -13,9 +13,12 @@
 
 use std::path::PathBuf;
 
+use horn_proto::combination::CombinationMode;
 use http::Uri;
 use up_transport_zenoh::zenoh_config::{self, Config};
 
+use crate::request_queue::OverflowPolicy;
+
 #[derive(clap::Parser, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Args {
     #[arg(short, long, env = "ZENOH_CONFIG", value_name = "PATH")]
@@ -31,6 +34,83 @@ pub struct Args {
     /// Enables the connection to the Kuksa Databroker.
     /// Otherwise the value of the horn signal is printed to the terminal only.
     pub kuksa_enabled: bool,
+
+    #[arg(long, env = "ACTUATOR_ZENOH_CONFIG", value_name = "PATH")]
+    /// A Zenoh configuration file for the Zenoh network of the horn actuator.
+    /// If set, the service tracks the liveliness token of the actuator and rejects
+    /// requests with UNAVAILABLE while the actuator is not connected.
+    actuator_config: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value = "Vehicle/Body/Horn/IsActive",
+        env = "ACTUATOR_KEY",
+        value_name = "KEY_EXPR"
+    )]
+    /// The key expression of the liveliness token declared by the horn actuator.
+    pub actuator_key: String,
+
+    #[arg(long, default_value = "false", env = "SYNC_ACK")]
+    /// Responds to requests only after the first switch of the horn reached Kuksa Databroker
+    /// or the terminal, with UNAVAILABLE or INTERNAL if it failed.
+    pub sync_ack: bool,
+
+    #[arg(
+        long,
+        default_value = "1000",
+        env = "ACK_TIMEOUT",
+        value_name = "MILLISECONDS"
+    )]
+    /// The time to wait for the switch of the horn in the synchronous-ack mode.
+    pub ack_timeout: u64,
+
+    #[arg(long, default_value = "4", env = "REQUEST_QUEUE_CAPACITY", value_parser = clap::value_parser!(u16).range(1..))]
+    /// The number of requests waiting for the request processor.
+    pub queue_capacity: u16,
+
+    #[arg(long, value_enum, default_value_t, env = "OVERFLOW_POLICY")]
+    /// What happens to a request while the request queue is full.
+    pub overflow_policy: OverflowPolicy,
+
+    #[arg(
+        long,
+        default_value = "500",
+        env = "REQUEST_QUEUE_TIMEOUT",
+        value_name = "MILLISECONDS"
+    )]
+    /// The time to wait for a free slot in the request queue with the policy `reject`,
+    /// before the request is rejected with RESOURCE_EXHAUSTED.
+    pub queue_timeout: u64,
+
+    #[arg(
+        long,
+        default_value = "preempt",
+        env = "COMBINATION_MODE",
+        value_name = "MODE"
+    )]
+    /// How a request is combined with a running request, unless the request selects a mode:
+    /// `preempt` replaces the running request, `queue` plays it afterwards and
+    /// `ignore-while-busy` rejects it with FAILED_PRECONDITION. The rejections are
+    /// answered right away, also without the synchronous acknowledgement.
+    pub combination_mode: CombinationMode,
+
+    #[arg(long, default_value = "4", env = "MAX_QUEUE_DEPTH")]
+    /// The number of requests waiting for the running request with the combination mode `queue`.
+    /// Further requests are rejected with RESOURCE_EXHAUSTED, as well as requests which
+    /// would wait for a continuous request (FAILED_PRECONDITION).
+    pub max_queue_depth: usize,
+
+    #[arg(
+        long,
+        default_value = "500",
+        env = "STATUS_INTERVAL",
+        value_name = "MILLISECONDS",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    /// The interval to repeat the current HornStatus on the topic 0x8000,
+    /// which is also published on every switch of the horn. Keep it below
+    /// the timeout of `horn-client status`.
+    pub status_interval: u64,
 }
 
 fn valid_uri(uri: &str) -> Result<Uri, String> {
@@ -45,4 +125,15 @@ impl Args {
             Ok(Config::default())
         }
     }
+
+    pub fn get_actuator_zenoh_config(
+        &self,
+    ) -> Result<Option<zenoh_config::Config>, Box<dyn std::error::Error>> {
+        self.actuator_config
+            .as_ref()
+            .map(|path| {
+                zenoh_config::Config::from_file(path).map_err(|e| e as Box<dyn std::error::Error>)
+            })
+            .transpose()
+    }
 }
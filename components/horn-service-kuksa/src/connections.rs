@@ -11,19 +11,72 @@
 * SPDX-License-Identifier: EPL-2.0
 *******************************************************************************/
 
+use horn_proto::status::Status;
 use http::Uri;
 use kuksa_rust_sdk::kuksa::common::ClientTraitV1;
 use kuksa_rust_sdk::kuksa::val::v1::KuksaClient;
 use kuksa_rust_sdk::v1_proto;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use protobuf::Enum;
 use std::collections::HashMap;
 use std::time::SystemTime;
 use tokio::select;
+use up_rust::UCode;
 
-pub(crate) async fn send_to_databroker(mut rx: tokio::sync::mpsc::Receiver<bool>, uri: Uri) {
+use crate::actuator::{self, ActuatorPresence};
+
+/// Confirms that a value of the horn signal reached the sink, or the status of the failure.
+pub(crate) type Ack = tokio::sync::oneshot::Sender<Result<(), Status>>;
+
+/// A value of the horn signal to write to the sink.
+pub(crate) struct Actuation {
+    pub is_active: bool,
+    /// Set in the synchronous-ack mode for the first actuation of a request.
+    pub ack: Option<Ack>,
+}
+
+impl Actuation {
+    fn reply(self, result: Result<(), Status>) {
+        if let Some(ack) = self.ack {
+            let _ = ack.send(result);
+        }
+    }
+}
+
+/// Sends a value of the horn signal to the sink, replying the ack with `INTERNAL` if the sink is gone.
+pub(crate) async fn actuate(tx_sink: &tokio::sync::mpsc::Sender<Actuation>, actuation: Actuation) {
+    if let Err(e) = tx_sink.send(actuation).await {
+        e.0.reply(Err(error_status(
+            UCode::INTERNAL,
+            "the sink of the horn signal is not running".to_string(),
+        )));
+    }
+}
+
+pub(crate) fn error_status(code: UCode, message: String) -> Status {
+    Status {
+        code: code.value(),
+        message,
+        ..Default::default()
+    }
+}
+
+pub(crate) async fn send_to_databroker(
+    mut rx: tokio::sync::mpsc::Receiver<Actuation>,
+    uri: Uri,
+    actuator: ActuatorPresence,
+) {
     info!("Connecting to Kuksa Databroker [{uri}]");
     let mut client = KuksaClient::new(uri);
-    while let Some(is_active) = rx.recv().await {
+    while let Some(actuation) = rx.recv().await {
+        let is_active = actuation.is_active;
+        // Switching the horn off is always passed on, so that the
+        // horn does not turn on when the actuator connects again
+        if is_active && !actuator.is_present() {
+            warn!("Not activating the horn since the horn actuator is not connected");
+            actuation.reply(Err(actuator::absent_status()));
+            continue;
+        }
         debug!("Sending: {:?}", is_active);
         let ts = Some(prost_types::Timestamp::from(SystemTime::now()));
         let datapoints = HashMap::from([(
@@ -33,17 +86,28 @@ pub(crate) async fn send_to_databroker(mut rx: tokio::sync::mpsc::Receiver<bool>
                 value: Some(v1_proto::datapoint::Value::Bool(is_active)),
             },
         )]);
-        if let Err(e) = client.set_target_values(datapoints).await {
-            error!("Failed to send the Horn signal to Kuksa Databroker: {e}");
+        match client.set_target_values(datapoints).await {
+            Ok(_) => actuation.reply(Ok(())),
+            Err(e) => {
+                error!("Failed to send the Horn signal to Kuksa Databroker: {e}");
+                actuation.reply(Err(error_status(
+                    UCode::UNAVAILABLE,
+                    format!("failed to write the horn signal to Kuksa Databroker: {e}"),
+                )));
+            }
         }
     }
 }
 
-pub(crate) async fn send_to_terminal(mut rx: tokio::sync::mpsc::Receiver<bool>) {
+pub(crate) async fn send_to_terminal(mut rx: tokio::sync::mpsc::Receiver<Actuation>) {
     let mut is_active = Some(false);
     while is_active.is_some() {
         is_active = select! {
-            next_is_active = rx.recv() => next_is_active,
+            next_actuation = rx.recv() => next_actuation.map(|actuation| {
+                let is_active = actuation.is_active;
+                actuation.reply(Ok(()));
+                is_active
+            }),
             _ = print_is_active(is_active.unwrap()) => is_active,
         }
     }
@@ -56,3 +120,74 @@ async fn print_is_active(is_active: bool) {
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use databroker_mock::{Faults, MockDatabroker};
+    use kuksa_rust_sdk::v1_proto::datapoint::Value;
+    use tokio::net::TcpListener;
+    use tokio::sync::{mpsc, oneshot};
+
+    async fn start_databroker(faults: Faults) -> (MockDatabroker, Uri) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let uri = format!("http://{}", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        let databroker = MockDatabroker::new(faults);
+        tokio::spawn(databroker.clone().serve_with_listener(listener));
+        (databroker, uri)
+    }
+
+    async fn switch(tx_sink: &mpsc::Sender<Actuation>, is_active: bool) -> Result<(), Status> {
+        let (ack, rx_ack) = oneshot::channel();
+        actuate(
+            tx_sink,
+            Actuation {
+                is_active,
+                ack: Some(ack),
+            },
+        )
+        .await;
+        rx_ack.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn actuations_are_written_as_target_values() {
+        let (databroker, uri) = start_databroker(Faults::default()).await;
+        let (tx_sink, rx_sink) = mpsc::channel(4);
+        tokio::spawn(send_to_databroker(
+            rx_sink,
+            uri,
+            ActuatorPresence::assumed(),
+        ));
+
+        assert!(switch(&tx_sink, true).await.is_ok());
+        assert!(switch(&tx_sink, false).await.is_ok());
+        let targets: Vec<_> = databroker
+            .target_value_calls()
+            .into_iter()
+            .map(|call| call.targets["Vehicle.Body.Horn.IsActive"].value.clone())
+            .collect();
+        assert_eq!(targets, [Some(Value::Bool(true)), Some(Value::Bool(false))]);
+    }
+
+    #[tokio::test]
+    async fn failed_writes_are_acked_with_unavailable() {
+        let (databroker, uri) = start_databroker(Faults {
+            failure: Some("unavailable".parse().unwrap()),
+            ..Default::default()
+        })
+        .await;
+        let (tx_sink, rx_sink) = mpsc::channel(4);
+        tokio::spawn(send_to_databroker(
+            rx_sink,
+            uri,
+            ActuatorPresence::assumed(),
+        ));
+
+        let status = switch(&tx_sink, true).await.unwrap_err();
+        assert_eq!(status.code, UCode::UNAVAILABLE.value());
+        assert_eq!(databroker.target_value_calls().len(), 1);
+    }
+}
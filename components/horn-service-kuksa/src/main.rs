@@ -13,29 +13,47 @@
 
 use clap::Parser;
 use env_logger::Env;
+use horn_proto::descriptors::horn;
+use horn_proto::rpc::register_horn_service;
 use log::info;
 use std::sync::Arc;
-use up_rust::communication::{InMemoryRpcServer, RpcServer};
+use std::time::Duration;
+use up_rust::communication::{InMemoryRpcServer, SimplePublisher};
 use up_transport_zenoh::UPTransportZenoh;
 
+use actuator::ActuatorPresence;
+
+mod actuator;
 mod config;
 mod connections;
 mod request_handler;
 mod request_processor;
+mod request_queue;
+mod schedule;
+mod status_publisher;
+mod timeline;
 
-const ACTIVATE_HORN_METHOD_ID: u16 = 0x0001;
-const DEACTIVATE_HORN_METHOD_ID: u16 = 0x0002;
+// The switches of the horn waiting for the sink. Kept small, so that a slow sink backs
+// up into the request queue, where the overflow policy applies.
+const SINK_CAPACITY: usize = 4;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
     info!("Starting the Horn service");
     let args = config::Args::parse();
-    let (tx_kuksa, rx_kuksa) = tokio::sync::mpsc::channel(32);
+    let actuator = match args.get_actuator_zenoh_config()? {
+        Some(actuator_config) => {
+            ActuatorPresence::track(actuator_config, args.actuator_key.clone()).await?
+        }
+        None => ActuatorPresence::assumed(),
+    };
+    let (tx_kuksa, rx_kuksa) = tokio::sync::mpsc::channel(SINK_CAPACITY);
     if args.kuksa_enabled {
         tokio::spawn(connections::send_to_databroker(
             rx_kuksa,
             args.kuksa_address.clone(),
+            actuator.clone(),
         ));
     } else {
         info!("Printing the horn signal to the terminal since the connection with Kuksa databroker is not enabled (use -k flag).");
@@ -44,26 +62,40 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let zenoh_config = args.get_zenoh_config()?;
     UPTransportZenoh::try_init_log_from_env();
-    let transport = UPTransportZenoh::new(zenoh_config, "//horn-service-kuksa/1C/1/0")
+    let transport = UPTransportZenoh::new(zenoh_config, horn::entity_uri("horn-service-kuksa")?)
         .await
         .map(Arc::new)?;
-    let rpc_server = InMemoryRpcServer::new(transport.clone(), transport);
+    let rpc_server = InMemoryRpcServer::new(transport.clone(), transport.clone());
+
+    let (tx_status, rx_status) = status_publisher::channel();
+    tokio::spawn(status_publisher::publish_status(
+        SimplePublisher::new(transport.clone(), transport),
+        rx_status,
+        actuator.clone(),
+        Duration::from_millis(args.status_interval),
+    ));
 
-    let (tx_sequence, rx_sequence) = tokio::sync::mpsc::channel(4);
+    let (tx_sequence, rx_sequence) = request_queue::channel(
+        usize::from(args.queue_capacity),
+        args.overflow_policy,
+        Duration::from_millis(args.queue_timeout),
+    );
     tokio::spawn(request_processor::receive_requests(
         rx_sequence,
         tx_kuksa.clone(),
+        tx_status,
     ));
 
-    let activate_horn_op = Arc::new(request_handler::ActivateHorn::new(tx_sequence.clone()));
-    rpc_server
-        .register_endpoint(None, ACTIVATE_HORN_METHOD_ID, activate_horn_op)
-        .await?;
-
-    let deactivate_horn_op = Arc::new(request_handler::DeactivateHorn::new(tx_sequence.clone()));
-    rpc_server
-        .register_endpoint(None, DEACTIVATE_HORN_METHOD_ID, deactivate_horn_op)
-        .await?;
+    let ack_timeout = args
+        .sync_ack
+        .then(|| Duration::from_millis(args.ack_timeout));
+    let horn_service = Arc::new(request_handler::HornRequestHandler::new(
+        tx_sequence,
+        schedule::Schedule::new(args.combination_mode, args.max_queue_depth),
+        actuator,
+        ack_timeout,
+    ));
+    register_horn_service(&rpc_server, horn_service).await?;
 
     std::thread::park();
     Ok(())
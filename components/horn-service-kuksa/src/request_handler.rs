@@ -11,87 +11,248 @@
 * SPDX-License-Identifier: EPL-2.0
 *******************************************************************************/
 
+use std::time::{Duration, SystemTime};
+
 use horn_proto::horn_service::{
     ActivateHornRequest, ActivateHornResponse, DeactivateHornRequest, DeactivateHornResponse,
 };
+use horn_proto::rpc::HornService;
 use horn_proto::status::Status;
-use log::info;
-use protobuf::MessageField;
-use up_rust::communication::{RequestHandler, ServiceInvocationError, UPayload};
+use log::{error, info, warn};
+use protobuf::{Enum, MessageField};
+use up_rust::communication::ServiceInvocationError;
+use up_rust::UCode;
+
+use crate::actuator::{self, ActuatorPresence};
+use crate::connections::error_status;
+use crate::request_processor::HornCommand;
+use crate::request_queue::{QueueSender, SendError};
+use crate::schedule::{Admission, Schedule};
+use crate::timeline::Timeline;
 
-pub(crate) struct ActivateHorn {
-    tx_sequence_channel: tokio::sync::mpsc::Sender<Option<ActivateHornRequest>>,
+pub(crate) struct HornRequestHandler {
+    tx_sequence_channel: QueueSender,
+    schedule: Schedule,
+    actuator: ActuatorPresence,
+    // Set in the synchronous-ack mode
+    ack_timeout: Option<Duration>,
 }
 
-impl ActivateHorn {
+impl HornRequestHandler {
     pub fn new(
-        tx_sequence_channel: tokio::sync::mpsc::Sender<Option<ActivateHornRequest>>,
+        tx_sequence_channel: QueueSender,
+        schedule: Schedule,
+        actuator: ActuatorPresence,
+        ack_timeout: Option<Duration>,
     ) -> Self {
         Self {
             tx_sequence_channel,
+            schedule,
+            actuator,
+            ack_timeout,
+        }
+    }
+
+    // Passes a command to the request processor. In the synchronous-ack mode, waits until
+    // the first actuation of the command reached the sink, which a queued request only
+    // does once it starts, so the planned wait extends the ack timeout.
+    async fn submit(
+        &self,
+        timeline: Option<Timeline>,
+        admission: Option<Admission>,
+    ) -> Result<(), Status> {
+        let Some(ack_timeout) = self.ack_timeout else {
+            return self
+                .send(HornCommand {
+                    timeline,
+                    ack: None,
+                    admission,
+                })
+                .await;
+        };
+        let ack_timeout = ack_timeout
+            + admission
+                .as_ref()
+                .map_or(Duration::ZERO, Admission::start_delay);
+        let (ack, rx_ack) = tokio::sync::oneshot::channel();
+        self.send(HornCommand {
+            timeline,
+            ack: Some(ack),
+            admission,
+        })
+        .await?;
+        match tokio::time::timeout(ack_timeout, rx_ack).await {
+            Ok(Ok(result)) => result.inspect_err(|status| {
+                warn!("The horn signal did not reach the sink: {}", status.message)
+            }),
+            Ok(Err(_)) => Err(error_status(
+                UCode::INTERNAL,
+                "the request was dropped before the horn signal reached the sink".to_string(),
+            )),
+            Err(_) => Err(error_status(
+                UCode::UNAVAILABLE,
+                format!(
+                    "the sink did not confirm the horn signal within {} ms",
+                    ack_timeout.as_millis()
+                ),
+            )),
         }
     }
+
+    async fn send(&self, command: HornCommand) -> Result<(), Status> {
+        self.tx_sequence_channel
+            .send(command)
+            .await
+            .map_err(|e| match e {
+                SendError::Full => {
+                    warn!("Rejecting the request since the request queue is full");
+                    error_status(
+                        UCode::RESOURCE_EXHAUSTED,
+                        "the request queue is full".to_string(),
+                    )
+                }
+                SendError::Closed => {
+                    error!(
+                        "Failed to pass the request on since the request processor is not running"
+                    );
+                    error_status(
+                        UCode::INTERNAL,
+                        "the request processor is not running".to_string(),
+                    )
+                }
+            })
+    }
 }
 
 #[async_trait::async_trait]
-impl RequestHandler for ActivateHorn {
-    async fn handle_request(
+impl HornService for HornRequestHandler {
+    async fn activate_horn(
         &self,
-        _resource_id: u16,
-        request_payload: Option<UPayload>,
-    ) -> Result<Option<UPayload>, ServiceInvocationError> {
+        req: ActivateHornRequest,
+    ) -> Result<ActivateHornResponse, ServiceInvocationError> {
         info!("Handle new request to apply horn sequence");
 
-        let req = request_payload
-            .unwrap()
-            .extract_protobuf::<ActivateHornRequest>()
-            .unwrap();
-        let _ = self.tx_sequence_channel.send(Some(req.clone())).await;
+        let timeline = req
+            .validate()
+            .map_err(|e| e.to_string())
+            .and_then(|_| Timeline::compile(&req));
+        let status = match timeline {
+            Err(e) => {
+                warn!("Rejecting the invalid horn sequence: {e}");
+                Status {
+                    code: UCode::INVALID_ARGUMENT.value(),
+                    message: e,
+                    ..Default::default()
+                }
+            }
+            Ok(timeline) if self.actuator.is_present() => {
+                match self
+                    .schedule
+                    .admit(timeline.duration(), req.combination_mode())
+                {
+                    Ok(admission) => {
+                        if admission.is_queued() {
+                            info!(
+                                "Queueing the request to start in {} ms",
+                                admission.start_delay().as_millis()
+                            );
+                        }
+                        let mut plan = Status::new();
+                        timeline.add_plan_details(
+                            &mut plan,
+                            SystemTime::now() + admission.start_delay(),
+                        );
+                        match self.submit(Some(timeline), Some(admission)).await {
+                            Ok(()) => plan,
+                            Err(status) => status,
+                        }
+                    }
+                    Err(status) => {
+                        warn!("Rejecting the horn sequence: {}", status.message);
+                        status
+                    }
+                }
+            }
+            Ok(_) => {
+                warn!("Rejecting the horn sequence since the horn actuator is not connected");
+                actuator::absent_status()
+            }
+        };
 
-        let response = ActivateHornResponse {
-            status: MessageField::some(Status::new()),
+        Ok(ActivateHornResponse {
+            status: MessageField::some(status),
             ..Default::default()
+        })
+    }
+
+    async fn deactivate_horn(
+        &self,
+        _req: DeactivateHornRequest,
+    ) -> Result<DeactivateHornResponse, ServiceInvocationError> {
+        info!("Handle new deactivation request for the horn.");
+
+        // Stop a running sequence in any case, but report that
+        // the horn cannot be switched off without the actuator
+        self.schedule.clear();
+        let result = self.submit(None, None).await;
+        let status = if !self.actuator.is_present() {
+            actuator::absent_status()
+        } else {
+            result.err().unwrap_or_default()
         };
-        let payload = UPayload::try_from_protobuf(response).unwrap();
-        Ok(Some(payload))
+        Ok(DeactivateHornResponse {
+            status: MessageField::some(status),
+            ..Default::default()
+        })
     }
 }
 
-pub(crate) struct DeactivateHorn {
-    tx_sequence_channel: tokio::sync::mpsc::Sender<Option<ActivateHornRequest>>,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_queue::{self, OverflowPolicy};
+    use horn_proto::combination::CombinationMode;
+    use horn_proto::pattern;
 
-impl DeactivateHorn {
-    pub fn new(
-        tx_sequence_channel: tokio::sync::mpsc::Sender<Option<ActivateHornRequest>>,
-    ) -> Self {
-        Self {
-            tx_sequence_channel,
-        }
+    async fn activate(
+        handler: &HornRequestHandler,
+        notation: &str,
+        combination_mode: CombinationMode,
+    ) -> i32 {
+        let mut request = pattern::parse_request(notation).unwrap();
+        request.set_combination_mode(combination_mode);
+        let response = handler.activate_horn(request).await.unwrap();
+        response.status.code
     }
-}
 
-#[async_trait::async_trait]
-impl RequestHandler for DeactivateHorn {
-    async fn handle_request(
-        &self,
-        _resource_id: u16,
-        request_payload: Option<UPayload>,
-    ) -> Result<Option<UPayload>, ServiceInvocationError> {
-        info!("Handle new deactivation request for the horn.");
+    #[tokio::test]
+    async fn rejected_preemptions_keep_the_planned_requests() {
+        let (tx_request, _rx_request) =
+            request_queue::channel(1, OverflowPolicy::Reject, Duration::from_millis(20));
+        let handler = HornRequestHandler::new(
+            tx_request,
+            Schedule::new(CombinationMode::Preempt, 4),
+            ActuatorPresence::assumed(),
+            None,
+        );
 
-        //Expect the deactivate horn request
-        //to be empty.
-        let _req = request_payload
-            .unwrap()
-            .extract_protobuf::<DeactivateHornRequest>()
-            .unwrap();
-        let _ = self.tx_sequence_channel.send(None).await;
-        let response = DeactivateHornResponse {
-            status: MessageField::some(Status::new()),
-            ..Default::default()
-        };
-        let payload = UPayload::try_from_protobuf(response).unwrap();
-        Ok(Some(payload))
+        // the first request fills the queue, which nobody takes from
+        assert_eq!(
+            activate(&handler, "continuous", CombinationMode::Preempt).await,
+            UCode::OK.value()
+        );
+        assert_eq!(
+            activate(&handler, "100/100", CombinationMode::Preempt).await,
+            UCode::RESOURCE_EXHAUSTED.value()
+        );
+        // the horn still counts as busy with the first request
+        assert_eq!(
+            activate(&handler, "100/100", CombinationMode::IgnoreWhileBusy).await,
+            UCode::FAILED_PRECONDITION.value()
+        );
+        assert_eq!(
+            activate(&handler, "100/100", CombinationMode::Queue).await,
+            UCode::FAILED_PRECONDITION.value()
+        );
     }
 }
@@ -11,72 +11,366 @@
 * SPDX-License-Identifier: EPL-2.0
 *******************************************************************************/
 
-use horn_proto::{horn_service::ActivateHornRequest, horn_topics::{HornMode, HornSequence}};
-use log::{debug, error};
+use std::collections::VecDeque;
+
+use log::{debug, info, warn};
 use tokio::select;
+use tokio::sync::mpsc::OwnedPermit;
+use up_rust::UCode;
+
+use crate::connections::{self, error_status, Ack, Actuation};
+use crate::request_queue::QueueReceiver;
+use crate::schedule::Admission;
+use crate::status_publisher::StatusSender;
+use crate::timeline::Timeline;
+
+/// A request to the processor: plays a timeline or, without one, deactivates the horn.
+pub(crate) struct HornCommand {
+    pub timeline: Option<Timeline>,
+    /// Replied when the first actuation of the command reached the sink, in the synchronous-ack mode.
+    pub ack: Option<Ack>,
+    /// The place of an activation in the schedule, which decided how it is combined with the
+    /// running request. An activation without admission replaces the running request.
+    pub admission: Option<Admission>,
+}
 
-// Listens to the request channel and applies the requests. When the channel returns 'None',
-// 'receive_requests' stops the execution of the previous request and the horn is deactived.
+impl HornCommand {
+    fn is_queued(&self) -> bool {
+        self.admission
+            .as_ref()
+            .is_some_and(|admission| admission.is_queued())
+    }
+}
+
+// Listens to the request channel and plays the timelines compiled from the requests. The request
+// handler already decided how a request is combined with the running one: a queued request is
+// played after the running and the previously queued requests, any other command replaces them.
+// A command without timeline deactivates the horn and discards the queued requests in any case.
 pub(crate) async fn receive_requests(
-    mut rx_request_channel: tokio::sync::mpsc::Receiver<Option<ActivateHornRequest>>, 
-    tx_kuksa: tokio::sync::mpsc::Sender<bool>) {
-    let mut request;
-    while let Some(request_inner) = rx_request_channel.recv().await {
-        request = Some(request_inner);
-        while request.is_some() {
-            request = select! {
-                req = rx_request_channel.recv() => req,
-                req = request_apply(request.unwrap(), tx_kuksa.clone()) => req,
-            }
+    mut rx_request_channel: QueueReceiver,
+    tx_kuksa: tokio::sync::mpsc::Sender<Actuation>,
+    tx_status: StatusSender,
+) {
+    let mut queued: VecDeque<HornCommand> = VecDeque::new();
+    let mut preempting = None;
+    loop {
+        let next = preempting
+            .take()
+            .or_else(|| queued.pop_front().map(|request| (request, None)));
+        let (request, slot) = match next {
+            Some(next) => next,
+            None => match next_command(&mut rx_request_channel, &tx_kuksa).await {
+                Some(next) => next,
+                None => return,
+            },
         };
+        if let Some(admission) = &request.admission {
+            if !admission.start() {
+                debug!("Skipping a request which was removed from the schedule");
+                abort(request);
+                continue;
+            }
+        }
+        let running = request_apply(request, slot, tx_kuksa.clone(), &tx_status);
+        tokio::pin!(running);
+        loop {
+            let next = select! {
+                _ = &mut running => break,
+                next = next_command(&mut rx_request_channel, &tx_kuksa) => next,
+            };
+            let Some((next, slot)) = next else {
+                return;
+            };
+            if next.is_queued() {
+                info!("Queueing the request behind {} requests", queued.len() + 1);
+                // the ack is kept until the request starts, the slot
+                // in the sink is released while the request waits
+                queued.push_back(next);
+            } else {
+                queued.drain(..).for_each(abort);
+                preempting = Some((next, slot));
+                // dropping the running request stops it
+                break;
+            }
+        }
     }
 }
 
-async fn request_apply(request: Option<ActivateHornRequest>, tx_kuksa: tokio::sync::mpsc::Sender<bool>) -> Option<Option<ActivateHornRequest>> {
-    match request {
-        Some(request_inner) => {
-            horn_request_apply(request_inner, tx_kuksa).await;
-            None
-        },
-        None => {
-            // treat None as a signal to deactivate the horn
-            let _ = tx_kuksa.send(false).await;
-            None
-        },
+// A slot in the sink reserved for the first actuation of a command
+type SinkSlot = OwnedPermit<Actuation>;
+
+// Takes the next command once the sink has room for an actuation, together with the slot
+// reserved for it. While the sink is backed up, e.g. by slow writes to Kuksa Databroker, the
+// activations stay in the request queue, so that its overflow policy applies. A deactivation
+// is taken right away, so that it stops the running request without waiting for the sink.
+async fn next_command(
+    rx_request_channel: &mut QueueReceiver,
+    tx_kuksa: &tokio::sync::mpsc::Sender<Actuation>,
+) -> Option<(HornCommand, Option<SinkSlot>)> {
+    if !rx_request_channel.ready().await {
+        return None;
+    }
+    select! {
+        biased;
+        _ = rx_request_channel.deactivation_next() => {
+            rx_request_channel.recv().await.map(|command| (command, None))
+        }
+        // without slot if the sink is gone, which the actuation reports
+        slot = tx_kuksa.clone().reserve_owned() => {
+            rx_request_channel.recv().await.map(|command| (command, slot.ok()))
+        }
+    }
+}
+
+// Sends the actuation through the reserved slot, if there is one
+async fn actuate(
+    slot: &mut Option<SinkSlot>,
+    tx_kuksa: &tokio::sync::mpsc::Sender<Actuation>,
+    actuation: Actuation,
+) {
+    match slot.take() {
+        Some(slot) => {
+            slot.send(actuation);
+        }
+        None => connections::actuate(tx_kuksa, actuation).await,
+    }
+}
+
+// Replies to a queued request which was replaced before it started
+fn abort(request: HornCommand) {
+    warn!("Dropping a queued request since a newer request replaces it");
+    if let Some(ack) = request.ack {
+        let _ = ack.send(Err(error_status(
+            UCode::ABORTED,
+            "the request was replaced before it started".to_string(),
+        )));
     }
 }
 
-pub async fn horn_request_apply(req: ActivateHornRequest, tx_kuksa: tokio::sync::mpsc::Sender<bool>) {
-    match req.mode.enum_value() {
-        Ok(mode) => {
-            match mode {
-                HornMode::HM_SEQUENCED => {
-                    let sequences = req.command;
-                    horn_sequence_apply(sequences, tx_kuksa).await;
+async fn request_apply(
+    request: HornCommand,
+    mut slot: Option<SinkSlot>,
+    tx_kuksa: tokio::sync::mpsc::Sender<Actuation>,
+    tx_status: &StatusSender,
+) {
+    // the request keeps its place in the schedule while it is played
+    let _admission = request.admission;
+    match request.timeline {
+        Some(timeline) => {
+            horn_timeline_apply(timeline, request.ack, slot, tx_kuksa, tx_status).await;
+        }
+        None => {
+            // treat a command without timeline as a signal to deactivate the horn
+            actuate(
+                &mut slot,
+                &tx_kuksa,
+                Actuation {
+                    is_active: false,
+                    ack: request.ack,
                 },
-                HornMode::HM_CONTINUOUS => horn_continous_apply(tx_kuksa).await,
-                HornMode::HM_UNKNOWN => println!("Horn Mode: Unknown"),
-                HornMode::HM_UNSPECIFIED => println!("Horn Mode: Unspecified"),
-            };
-        },
-        Err(e) => error!("Error in Horn Mode value {:?}", e),
+            )
+            .await;
+            tx_status.send_replace(Default::default());
+        }
+    }
+}
+
+// Plays the transitions of the timeline at their points in time. A timeline with a
+// duration is completed after its last off time, a continuous one is never completed,
+// so that the horn counts as busy until it is deactivated or preempted.
+// The ack and the reserved slot are used for the first transition, the status is published
+// after each one.
+async fn horn_timeline_apply(
+    timeline: Timeline,
+    mut ack: Option<Ack>,
+    mut slot: Option<SinkSlot>,
+    tx_kuksa: tokio::sync::mpsc::Sender<Actuation>,
+    tx_status: &StatusSender,
+) {
+    let start = tokio::time::Instant::now();
+    if timeline.transitions().is_empty() {
+        if let Some(ack) = ack.take() {
+            let _ = ack.send(Ok(()));
+        }
+    }
+    for transition in timeline.transitions() {
+        tokio::time::sleep_until(start + transition.at).await;
+        match transition.position {
+            Some((sequence, cycle)) if transition.is_active => debug!(
+                "Horn on, sequence {} of {}, cycle {}",
+                sequence + 1,
+                timeline.total_sequences(),
+                cycle + 1
+            ),
+            Some(_) => debug!("Horn off"),
+            None => debug!("Starting Continous Horn"),
+        }
+        let actuation = Actuation {
+            is_active: transition.is_active,
+            ack: ack.take(),
         };
+        actuate(&mut slot, &tx_kuksa, actuation).await;
+        tx_status.send_replace(timeline.status(transition));
+    }
+    match timeline.duration() {
+        Some(duration) => tokio::time::sleep_until(start + duration).await,
+        None => std::future::pending().await,
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_queue::{self, OverflowPolicy, SendError};
+    use crate::schedule::Schedule;
+    use crate::status_publisher;
+    use horn_proto::combination::CombinationMode;
+    use horn_proto::pattern;
+    use horn_proto::status::Status;
+    use protobuf::Enum;
+    use std::time::Duration;
+    use tokio::sync::oneshot;
 
-pub async fn horn_continous_apply(tx_kuksa: tokio::sync::mpsc::Sender<bool>) {
-    debug!("Starting Continous Horn");
-    let _ = tx_kuksa.send(true).await;
-}
+    type AckReceiver = oneshot::Receiver<Result<(), Status>>;
+
+    fn activation(notation: &str) -> HornCommand {
+        let request = pattern::parse_request(notation).unwrap();
+        HornCommand {
+            timeline: Some(Timeline::compile(&request).unwrap()),
+            ack: None,
+            admission: None,
+        }
+    }
+
+    // An activation admitted to the schedule with the given combination mode
+    fn admitted(
+        schedule: &Schedule,
+        notation: &str,
+        combination_mode: CombinationMode,
+    ) -> (HornCommand, AckReceiver) {
+        let mut command = activation(notation);
+        let duration = command.timeline.as_ref().unwrap().duration();
+        command.admission = Some(schedule.admit(duration, Some(combination_mode)).unwrap());
+        let (ack, rx_ack) = oneshot::channel();
+        command.ack = Some(ack);
+        (command, rx_ack)
+    }
 
-pub async fn horn_sequence_apply(sequences: Vec<HornSequence>, tx_kuksa: tokio::sync::mpsc::Sender<bool>) {
-    for sequence in sequences {
-        for cycle in sequence.horn_cycles {
-            debug!("\nOn Time: {}, Off Time: {}", cycle.on_time, cycle.off_time);
-            let _ = tx_kuksa.send(true).await;
-            let _ = tokio::time::sleep(std::time::Duration::from_millis(cycle.on_time as u64)).await;
-            let _ = tx_kuksa.send(false).await;
-            let _ = tokio::time::sleep(std::time::Duration::from_millis(cycle.off_time as u64)).await;
+    fn spawn_processor() -> (
+        request_queue::QueueSender,
+        tokio::sync::mpsc::Receiver<Actuation>,
+    ) {
+        let (tx_kuksa, rx_kuksa) = tokio::sync::mpsc::channel(4);
+        let (tx_status, _rx_status) = status_publisher::channel();
+        let (tx_request, rx_request) =
+            request_queue::channel(4, OverflowPolicy::Reject, Duration::from_millis(20));
+        tokio::spawn(receive_requests(rx_request, tx_kuksa, tx_status));
+        (tx_request, rx_kuksa)
+    }
+
+    // Confirms the actuation to the request handler, like the sink does
+    fn confirm(actuation: Actuation) -> bool {
+        if let Some(ack) = actuation.ack {
+            let _ = ack.send(Ok(()));
         }
+        actuation.is_active
+    }
+
+    #[tokio::test]
+    async fn requests_stay_queued_while_the_sink_is_backed_up() {
+        let (tx_kuksa, mut rx_kuksa) = tokio::sync::mpsc::channel(1);
+        let (tx_status, _rx_status) = status_publisher::channel();
+        let (tx_request, rx_request) =
+            request_queue::channel(1, OverflowPolicy::Reject, Duration::from_millis(20));
+        tokio::spawn(receive_requests(rx_request, tx_kuksa, tx_status));
+
+        // the switch of the first request occupies the sink
+        assert!(tx_request.send(activation("continuous")).await.is_ok());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(tx_request.send(activation("100/100")).await.is_ok());
+        assert!(matches!(
+            tx_request.send(activation("200/200")).await,
+            Err(SendError::Full)
+        ));
+
+        // once the sink took the switch, the queued request preempts the first one
+        assert!(rx_kuksa.recv().await.unwrap().is_active);
+        assert!(rx_kuksa.recv().await.unwrap().is_active);
+        assert!(!rx_kuksa.recv().await.unwrap().is_active);
+    }
+
+    #[tokio::test]
+    async fn deactivations_pass_the_backed_up_sink() {
+        let (tx_kuksa, mut rx_kuksa) = tokio::sync::mpsc::channel(1);
+        let (tx_status, _rx_status) = status_publisher::channel();
+        let (tx_request, rx_request) =
+            request_queue::channel(1, OverflowPolicy::Reject, Duration::from_millis(20));
+        tokio::spawn(receive_requests(rx_request, tx_kuksa, tx_status));
+
+        // the switch of the first request occupies the sink
+        assert!(tx_request.send(activation("continuous")).await.is_ok());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(tx_request.send(activation("100/100")).await.is_ok());
+        let deactivation = HornCommand {
+            timeline: None,
+            ack: None,
+            admission: None,
+        };
+        assert!(tx_request.send(deactivation).await.is_ok());
+
+        // the deactivation left the request queue, the activation was replaced by it
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(tx_request.send(activation("200/200")).await.is_ok());
+        assert!(rx_kuksa.recv().await.unwrap().is_active);
+        assert!(!rx_kuksa.recv().await.unwrap().is_active);
+        assert!(rx_kuksa.recv().await.unwrap().is_active);
+    }
+
+    #[tokio::test]
+    async fn queued_requests_are_acked_when_they_start() {
+        let schedule = Schedule::new(CombinationMode::Queue, 4);
+        let (tx_request, mut rx_kuksa) = spawn_processor();
+
+        let (running, mut rx_running) = admitted(&schedule, "50/10", CombinationMode::Queue);
+        let (queued, mut rx_queued) = admitted(&schedule, "30/10", CombinationMode::Queue);
+        assert!(tx_request.send(running).await.is_ok());
+        assert!(confirm(rx_kuksa.recv().await.unwrap()));
+        assert!(tx_request.send(queued).await.is_ok());
+        assert!(matches!(rx_running.try_recv(), Ok(Ok(()))));
+
+        // the queued request waits for the first one
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(rx_queued.try_recv().is_err());
+        assert!(!confirm(rx_kuksa.recv().await.unwrap()));
+        assert!(rx_queued.try_recv().is_err());
+        assert!(confirm(rx_kuksa.recv().await.unwrap()));
+        assert!(matches!(rx_queued.await, Ok(Ok(()))));
+        assert!(!confirm(rx_kuksa.recv().await.unwrap()));
+
+        // completed requests leave the schedule
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(schedule.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn preempting_requests_abort_the_queued_ones() {
+        let schedule = Schedule::new(CombinationMode::Queue, 4);
+        let (tx_request, mut rx_kuksa) = spawn_processor();
+
+        let (running, _) = admitted(&schedule, "continuous", CombinationMode::Preempt);
+        assert!(tx_request.send(running).await.is_ok());
+        assert!(confirm(rx_kuksa.recv().await.unwrap()));
+        let (running, _) = admitted(&schedule, "100/100", CombinationMode::Preempt);
+        let (queued, rx_queued) = admitted(&schedule, "200/200", CombinationMode::Queue);
+        assert!(tx_request.send(running).await.is_ok());
+        assert!(tx_request.send(queued).await.is_ok());
+        assert!(confirm(rx_kuksa.recv().await.unwrap()));
+
+        let (preempting, _) = admitted(&schedule, "300/300", CombinationMode::Preempt);
+        assert!(tx_request.send(preempting).await.is_ok());
+        let status = rx_queued.await.unwrap().unwrap_err();
+        assert_eq!(status.code, UCode::ABORTED.value());
+        assert!(confirm(rx_kuksa.recv().await.unwrap()));
+        assert_eq!(schedule.len(), 1);
     }
 }
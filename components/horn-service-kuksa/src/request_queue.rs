@@ -0,0 +1,424 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use log::warn;
+use tokio::sync::Notify;
+use up_rust::UCode;
+
+use crate::connections::error_status;
+use crate::request_processor::HornCommand;
+
+/// What happens to a command passed to the request processor while the request queue is full.
+/// Queued deactivations are never dropped.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// Waits for a free slot up to the queue timeout, then rejects the command.
+    #[default]
+    Reject,
+    /// Drops the oldest queued activation to make room for the new one.
+    DropOldest,
+    /// Replaces all queued activations with the new one, which supersedes them.
+    Coalesce,
+}
+
+/// The reason why a command could not be queued.
+pub(crate) enum SendError {
+    /// The queue stayed full for the queue timeout.
+    Full,
+    /// The request processor is not running.
+    Closed,
+}
+
+struct State {
+    commands: VecDeque<HornCommand>,
+    senders: usize,
+    closed: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    not_empty: Notify,
+    not_full: Notify,
+}
+
+impl Shared {
+    // The state is only changed by operations which don't panic, so it is
+    // consistent even if a thread panicked while holding the lock.
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Creates a bounded queue of commands for the request processor.
+pub(crate) fn channel(
+    capacity: usize,
+    policy: OverflowPolicy,
+    timeout: Duration,
+) -> (QueueSender, QueueReceiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            commands: VecDeque::with_capacity(capacity),
+            senders: 1,
+            closed: false,
+        }),
+        capacity: capacity.max(1),
+        policy,
+        not_empty: Notify::new(),
+        not_full: Notify::new(),
+    });
+    (
+        QueueSender {
+            shared: shared.clone(),
+            timeout,
+        },
+        QueueReceiver { shared },
+    )
+}
+
+pub(crate) struct QueueSender {
+    shared: Arc<Shared>,
+    // The maximum time to wait for a free slot with the policy `reject`
+    timeout: Duration,
+}
+
+impl QueueSender {
+    /// Queues a command according to the overflow policy. A deactivation replaces all
+    /// queued commands, since the request processor would discard them anyway. The acks
+    /// of commands dropped from the queue are replied with `RESOURCE_EXHAUSTED` or `ABORTED`.
+    pub async fn send(&self, command: HornCommand) -> Result<(), SendError> {
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        loop {
+            let not_full = self.shared.not_full.notified();
+            {
+                let mut state = self.shared.state();
+                if state.closed {
+                    return Err(SendError::Closed);
+                }
+                let dropped = if command.timeline.is_none() {
+                    Some((DropReason::Superseded, state.commands.drain(..).collect()))
+                } else if state.commands.len() < self.shared.capacity {
+                    Some((DropReason::Superseded, Vec::new()))
+                } else {
+                    self.make_room(&mut state.commands)
+                };
+                if let Some((reason, dropped)) = dropped {
+                    state.commands.push_back(command);
+                    self.shared.not_empty.notify_one();
+                    drop(state);
+                    reply_dropped(reason, dropped);
+                    return Ok(());
+                }
+            }
+            if tokio::time::timeout_at(deadline, not_full).await.is_err() {
+                return Err(SendError::Full);
+            }
+        }
+    }
+
+    // Removes queued activations from the full queue according to the overflow policy,
+    // None if the new command has to wait.
+    fn make_room(
+        &self,
+        commands: &mut VecDeque<HornCommand>,
+    ) -> Option<(DropReason, Vec<HornCommand>)> {
+        match self.shared.policy {
+            OverflowPolicy::Reject => None,
+            OverflowPolicy::DropOldest => {
+                let oldest = commands
+                    .iter()
+                    .position(|command| command.timeline.is_some())?;
+                Some((
+                    DropReason::Overflow,
+                    commands.remove(oldest).into_iter().collect(),
+                ))
+            }
+            OverflowPolicy::Coalesce => {
+                let (activations, deactivations): (Vec<_>, _) = std::mem::take(commands)
+                    .into_iter()
+                    .partition(|command| command.timeline.is_some());
+                *commands = deactivations.into();
+                Some((DropReason::Superseded, activations))
+            }
+        }
+    }
+}
+
+// Why queued commands are dropped
+#[derive(Clone, Copy)]
+enum DropReason {
+    Overflow,
+    Superseded,
+}
+
+fn reply_dropped(reason: DropReason, dropped: Vec<HornCommand>) {
+    for command in dropped {
+        let status = match reason {
+            DropReason::Overflow => {
+                warn!("Dropping the oldest queued request since the request queue is full");
+                error_status(
+                    UCode::RESOURCE_EXHAUSTED,
+                    "the request was dropped from the full request queue".to_string(),
+                )
+            }
+            DropReason::Superseded => {
+                warn!("Dropping a queued request since a newer request supersedes it");
+                error_status(
+                    UCode::ABORTED,
+                    "the request was superseded by a newer request".to_string(),
+                )
+            }
+        };
+        if let Some(ack) = command.ack {
+            let _ = ack.send(Err(status));
+        }
+    }
+}
+
+impl Clone for QueueSender {
+    fn clone(&self) -> Self {
+        self.shared.state().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+            timeout: self.timeout,
+        }
+    }
+}
+
+impl Drop for QueueSender {
+    fn drop(&mut self) {
+        let mut state = self.shared.state();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.shared.not_empty.notify_one();
+        }
+    }
+}
+
+pub(crate) struct QueueReceiver {
+    shared: Arc<Shared>,
+}
+
+impl QueueReceiver {
+    /// Waits until a command is queued, false when all senders are gone.
+    pub async fn ready(&mut self) -> bool {
+        loop {
+            let not_empty = self.shared.not_empty.notified();
+            {
+                let state = self.shared.state();
+                if !state.commands.is_empty() {
+                    return true;
+                }
+                if state.senders == 0 {
+                    return false;
+                }
+            }
+            not_empty.await;
+        }
+    }
+
+    /// Waits until a deactivation is the next command, which it is as soon
+    /// as it is queued, since it replaces all queued commands.
+    pub async fn deactivation_next(&mut self) {
+        loop {
+            let not_empty = self.shared.not_empty.notified();
+            {
+                let state = self.shared.state();
+                let next = state.commands.front();
+                if next.is_some_and(|command| command.timeline.is_none()) {
+                    return;
+                }
+            }
+            not_empty.await;
+        }
+    }
+
+    /// Returns the next command, or None when all senders are gone.
+    /// The future can be cancelled without losing a command.
+    pub async fn recv(&mut self) -> Option<HornCommand> {
+        loop {
+            let not_empty = self.shared.not_empty.notified();
+            {
+                let mut state = self.shared.state();
+                if let Some(command) = state.commands.pop_front() {
+                    self.shared.not_full.notify_one();
+                    return Some(command);
+                }
+                if state.senders == 0 {
+                    return None;
+                }
+            }
+            not_empty.await;
+        }
+    }
+}
+
+impl Drop for QueueReceiver {
+    fn drop(&mut self) {
+        self.shared.state().closed = true;
+        self.shared.not_full.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connections::Ack;
+    use crate::timeline::Timeline;
+    use horn_proto::pattern;
+    use horn_proto::status::Status;
+    use protobuf::Enum;
+    use tokio::sync::oneshot;
+
+    type AckReceiver = oneshot::Receiver<Result<(), Status>>;
+
+    const TIMEOUT: Duration = Duration::from_millis(20);
+
+    // An activation identified by its on time
+    fn activation(on_time: u32) -> (HornCommand, AckReceiver) {
+        let request = pattern::parse_request(&format!("{on_time}/30")).unwrap();
+        command(Some(Timeline::compile(&request).unwrap()))
+    }
+
+    fn deactivation() -> (HornCommand, AckReceiver) {
+        command(None)
+    }
+
+    fn command(timeline: Option<Timeline>) -> (HornCommand, AckReceiver) {
+        let (ack, rx_ack): (Ack, _) = oneshot::channel();
+        let command = HornCommand {
+            timeline,
+            ack: Some(ack),
+            admission: None,
+        };
+        (command, rx_ack)
+    }
+
+    // The on time of a received activation, None for a deactivation
+    async fn next(rx: &mut QueueReceiver) -> Option<u128> {
+        let command = rx.recv().await.unwrap();
+        command
+            .timeline
+            .map(|timeline| timeline.duration().unwrap().as_millis() - 30)
+    }
+
+    async fn fill(tx: &QueueSender, on_times: &[u32]) -> Vec<AckReceiver> {
+        let mut acks = Vec::new();
+        for on_time in on_times {
+            let (command, rx_ack) = activation(*on_time);
+            assert!(tx.send(command).await.is_ok());
+            acks.push(rx_ack);
+        }
+        acks
+    }
+
+    // The code an ack was replied with, None if it was not replied yet
+    fn code(rx_ack: &mut AckReceiver) -> Option<i32> {
+        match rx_ack.try_recv() {
+            Ok(Err(status)) => Some(status.code),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn commands_are_received_in_order() {
+        let (tx, mut rx) = channel(3, OverflowPolicy::Reject, TIMEOUT);
+        fill(&tx, &[100, 200, 300]).await;
+        assert_eq!(next(&mut rx).await, Some(100));
+        fill(&tx, &[400]).await;
+        assert_eq!(next(&mut rx).await, Some(200));
+        assert_eq!(next(&mut rx).await, Some(300));
+        assert_eq!(next(&mut rx).await, Some(400));
+        drop(tx);
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn reject_waits_for_the_timeout() {
+        let (tx, mut rx) = channel(2, OverflowPolicy::Reject, TIMEOUT);
+        let mut acks = fill(&tx, &[100, 200]).await;
+        let start = tokio::time::Instant::now();
+        let (command, _) = activation(300);
+        assert!(matches!(tx.send(command).await, Err(SendError::Full)));
+        assert!(start.elapsed() >= TIMEOUT);
+        // the queued commands are kept
+        assert_eq!(code(&mut acks[0]), None);
+        assert_eq!(next(&mut rx).await, Some(100));
+        fill(&tx, &[300]).await;
+        assert_eq!(next(&mut rx).await, Some(200));
+        assert_eq!(next(&mut rx).await, Some(300));
+    }
+
+    #[tokio::test]
+    async fn reject_takes_a_slot_freed_in_time() {
+        let (tx, mut rx) = channel(1, OverflowPolicy::Reject, Duration::from_secs(10));
+        fill(&tx, &[100]).await;
+        let receiver = tokio::spawn(async move {
+            tokio::time::sleep(TIMEOUT).await;
+            let first = next(&mut rx).await;
+            (first, next(&mut rx).await)
+        });
+        fill(&tx, &[200]).await;
+        assert_eq!(receiver.await.unwrap(), (Some(100), Some(200)));
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_acks_the_dropped_command() {
+        let (tx, mut rx) = channel(2, OverflowPolicy::DropOldest, TIMEOUT);
+        let mut acks = fill(&tx, &[100, 200, 300]).await;
+        assert_eq!(code(&mut acks[0]), Some(UCode::RESOURCE_EXHAUSTED.value()));
+        assert_eq!(code(&mut acks[1]), None);
+        assert_eq!(next(&mut rx).await, Some(200));
+        assert_eq!(next(&mut rx).await, Some(300));
+    }
+
+    #[tokio::test]
+    async fn coalesce_aborts_the_superseded_commands() {
+        let (tx, mut rx) = channel(2, OverflowPolicy::Coalesce, TIMEOUT);
+        let mut acks = fill(&tx, &[100, 200, 300]).await;
+        assert_eq!(code(&mut acks[0]), Some(UCode::ABORTED.value()));
+        assert_eq!(code(&mut acks[1]), Some(UCode::ABORTED.value()));
+        assert_eq!(code(&mut acks[2]), None);
+        assert_eq!(next(&mut rx).await, Some(300));
+        drop(tx);
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn deactivations_flush_the_queue_and_are_never_dropped() {
+        let (tx, mut rx) = channel(2, OverflowPolicy::DropOldest, TIMEOUT);
+        let mut acks = fill(&tx, &[100, 200]).await;
+        let (command, _) = deactivation();
+        assert!(tx.send(command).await.is_ok());
+        assert_eq!(code(&mut acks[0]), Some(UCode::ABORTED.value()));
+        assert_eq!(code(&mut acks[1]), Some(UCode::ABORTED.value()));
+
+        let mut acks = fill(&tx, &[300, 400]).await;
+        assert_eq!(code(&mut acks[0]), Some(UCode::RESOURCE_EXHAUSTED.value()));
+        assert_eq!(next(&mut rx).await, None);
+        assert_eq!(next(&mut rx).await, Some(400));
+    }
+
+    #[tokio::test]
+    async fn closed_queues_refuse_commands() {
+        let (tx, rx) = channel(2, OverflowPolicy::Reject, TIMEOUT);
+        drop(rx);
+        let (command, _) = activation(100);
+        assert!(matches!(tx.send(command).await, Err(SendError::Closed)));
+    }
+}
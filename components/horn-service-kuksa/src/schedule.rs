@@ -0,0 +1,355 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use horn_proto::combination::CombinationMode;
+use horn_proto::status::Status;
+use tokio::time::Instant;
+use up_rust::UCode;
+
+use crate::connections::error_status;
+
+/// The requests the horn plays and is going to play, shared by the request handler, which
+/// admits requests according to their combination mode, and the request processor.
+#[derive(Clone)]
+pub(crate) struct Schedule {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    plan: Mutex<Plan>,
+    combination_mode: CombinationMode,
+    max_queue_depth: usize,
+}
+
+#[derive(Default)]
+struct Plan {
+    next_id: u64,
+    // The running request first, followed by the queued ones
+    entries: VecDeque<Entry>,
+}
+
+struct Entry {
+    id: u64,
+    // None for a continuous request
+    duration: Option<Duration>,
+    // Set once the request processor started the request
+    started: Option<Instant>,
+    // The preempting request which replaces this one once it starts
+    preempted_by: Option<u64>,
+}
+
+impl Plan {
+    // The requests which are not about to be replaced
+    fn live(&self) -> impl Iterator<Item = &Entry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.preempted_by.is_none())
+    }
+
+    // The time when the last planned request ends, None if one of them lasts until it is deactivated
+    fn end(&self, now: Instant) -> Option<Instant> {
+        let mut end = now;
+        for entry in self.live() {
+            end = entry.started.unwrap_or(end) + entry.duration?;
+        }
+        Some(end.max(now))
+    }
+
+    // Removes the requests replaced by the started request, as well as
+    // the ones which those requests would have replaced in turn.
+    fn remove_preempted(&mut self, id: u64) {
+        let mut replaced = vec![id];
+        while let Some(id) = replaced.pop() {
+            self.entries.retain(|entry| {
+                if entry.preempted_by == Some(id) {
+                    replaced.push(entry.id);
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+    }
+}
+
+impl Schedule {
+    /// An empty schedule applying the given combination mode to requests which don't select one.
+    pub fn new(combination_mode: CombinationMode, max_queue_depth: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                plan: Mutex::new(Plan::default()),
+                combination_mode,
+                max_queue_depth,
+            }),
+        }
+    }
+
+    // The plan is only changed by operations which don't panic, so it is
+    // consistent even if a thread panicked while holding the lock.
+    fn plan(&self) -> MutexGuard<'_, Plan> {
+        self.shared
+            .plan
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Decides how a request with the given duration, None for a continuous request, is
+    /// combined with the planned requests. The request is rejected with `FAILED_PRECONDITION`
+    /// if it is ignored while the horn is busy or would wait for a continuous request, and
+    /// with `RESOURCE_EXHAUSTED` if too many requests are queued.
+    pub fn admit(
+        &self,
+        duration: Option<Duration>,
+        combination_mode: Option<CombinationMode>,
+    ) -> Result<Admission, Status> {
+        let now = Instant::now();
+        let mut plan = self.plan();
+        let mut start = now;
+        let mut queued = false;
+        let id = plan.next_id + 1;
+        if plan.live().next().is_some() {
+            match combination_mode.unwrap_or(self.shared.combination_mode) {
+                // The planned requests are only removed once the new one starts,
+                // since they keep playing if it doesn't reach the request processor
+                CombinationMode::Preempt => plan
+                    .entries
+                    .iter_mut()
+                    .filter(|entry| entry.preempted_by.is_none())
+                    .for_each(|entry| entry.preempted_by = Some(id)),
+                CombinationMode::Queue => {
+                    let Some(end) = plan.end(now) else {
+                        return Err(error_status(
+                            UCode::FAILED_PRECONDITION,
+                            "the horn is busy with a continuous request until it is deactivated"
+                                .to_string(),
+                        ));
+                    };
+                    let waiting = plan.live().count() - 1;
+                    if waiting >= self.shared.max_queue_depth {
+                        return Err(error_status(
+                            UCode::RESOURCE_EXHAUSTED,
+                            format!("{waiting} requests are already queued"),
+                        ));
+                    }
+                    start = end;
+                    queued = true;
+                }
+                CombinationMode::IgnoreWhileBusy => {
+                    return Err(error_status(
+                        UCode::FAILED_PRECONDITION,
+                        "the horn is playing another request".to_string(),
+                    ))
+                }
+            }
+        }
+        plan.next_id = id;
+        plan.entries.push_back(Entry {
+            id,
+            duration,
+            started: None,
+            preempted_by: None,
+        });
+        Ok(Admission {
+            schedule: self.clone(),
+            id,
+            queued,
+            start_delay: start - now,
+        })
+    }
+
+    /// Removes all requests for a deactivation, which stops them.
+    pub fn clear(&self) {
+        self.plan().entries.clear();
+    }
+
+    /// The number of planned requests, including the running one,
+    /// without the ones which are about to be replaced.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.plan().live().count()
+    }
+}
+
+/// The place of an admitted request in the schedule, which the request leaves when it is dropped.
+/// A preempting request only removes the planned requests once it starts, so that they are
+/// restored if it is dropped before, e.g. because the request queue is full.
+pub(crate) struct Admission {
+    schedule: Schedule,
+    id: u64,
+    queued: bool,
+    start_delay: Duration,
+}
+
+impl Admission {
+    /// Whether the request waits for the planned requests, instead of replacing them.
+    pub fn is_queued(&self) -> bool {
+        self.queued
+    }
+
+    /// The planned time from the admission until the request starts.
+    pub fn start_delay(&self) -> Duration {
+        self.start_delay
+    }
+
+    /// Marks the request as started and removes the requests it preempts, false if it
+    /// was removed from the schedule in the meantime by a preempting request or a deactivation.
+    pub fn start(&self) -> bool {
+        let mut plan = self.schedule.plan();
+        let Some(entry) = plan.entries.iter_mut().find(|entry| entry.id == self.id) else {
+            return false;
+        };
+        entry.started = Some(Instant::now());
+        plan.remove_preempted(self.id);
+        true
+    }
+}
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        let mut plan = self.schedule.plan();
+        let Some(position) = plan.entries.iter().position(|entry| entry.id == self.id) else {
+            return;
+        };
+        // the requests this one would have replaced are restored,
+        // unless a newer request is about to replace them as well
+        let heir = plan
+            .entries
+            .remove(position)
+            .and_then(|entry| entry.preempted_by);
+        for entry in plan.entries.iter_mut() {
+            if entry.preempted_by == Some(self.id) {
+                entry.preempted_by = heir;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protobuf::Enum;
+
+    const SECOND: Option<Duration> = Some(Duration::from_secs(1));
+
+    fn code(result: Result<Admission, Status>) -> i32 {
+        match result {
+            Ok(_) => UCode::OK.value(),
+            Err(status) => status.code,
+        }
+    }
+
+    #[test]
+    fn idle_horn_starts_requests_right_away() {
+        let schedule = Schedule::new(CombinationMode::IgnoreWhileBusy, 2);
+        let admission = schedule
+            .admit(SECOND, Some(CombinationMode::Queue))
+            .unwrap();
+        assert!(!admission.is_queued());
+        assert_eq!(admission.start_delay(), Duration::ZERO);
+        assert_eq!(schedule.len(), 1);
+        drop(admission);
+        assert_eq!(schedule.len(), 0);
+    }
+
+    #[test]
+    fn queued_requests_start_after_the_planned_ones() {
+        let schedule = Schedule::new(CombinationMode::Queue, 2);
+        let running = schedule.admit(SECOND, None).unwrap();
+        assert!(running.start());
+        let first = schedule.admit(Some(Duration::from_secs(2)), None).unwrap();
+        let second = schedule.admit(SECOND, None).unwrap();
+        assert!(first.is_queued() && second.is_queued());
+        assert!(first.start_delay() <= Duration::from_secs(1));
+        assert!(first.start_delay() > Duration::from_millis(900));
+        assert!(second.start_delay() > Duration::from_millis(2900));
+
+        let third = schedule.admit(SECOND, None);
+        assert_eq!(code(third), UCode::RESOURCE_EXHAUSTED.value());
+        // a dropped request frees its place
+        drop(first);
+        assert!(schedule.admit(SECOND, None).is_ok());
+    }
+
+    #[test]
+    fn continuous_requests_cant_be_queued_behind() {
+        let schedule = Schedule::new(CombinationMode::Queue, 2);
+        let _running = schedule.admit(None, None).unwrap();
+        assert_eq!(
+            code(schedule.admit(SECOND, None)),
+            UCode::FAILED_PRECONDITION.value()
+        );
+        assert!(schedule
+            .admit(SECOND, Some(CombinationMode::Preempt))
+            .is_ok());
+    }
+
+    #[test]
+    fn dropped_preempting_requests_restore_the_planned_ones() {
+        let schedule = Schedule::new(CombinationMode::Queue, 2);
+        let running = schedule.admit(SECOND, None).unwrap();
+        assert!(running.start());
+        let queued = schedule.admit(SECOND, None).unwrap();
+        let preempting = schedule
+            .admit(SECOND, Some(CombinationMode::Preempt))
+            .unwrap();
+        let superseding = schedule
+            .admit(None, Some(CombinationMode::Preempt))
+            .unwrap();
+        assert_eq!(schedule.len(), 1);
+
+        // the requests pass on to the newest preempting request
+        drop(preempting);
+        assert_eq!(schedule.len(), 1);
+        drop(superseding);
+        assert_eq!(schedule.len(), 2);
+        assert!(queued.start());
+        assert_eq!(
+            code(schedule.admit(SECOND, Some(CombinationMode::IgnoreWhileBusy))),
+            UCode::FAILED_PRECONDITION.value()
+        );
+    }
+
+    #[test]
+    fn busy_horn_ignores_requests() {
+        let schedule = Schedule::new(CombinationMode::IgnoreWhileBusy, 2);
+        let _running = schedule.admit(SECOND, None).unwrap();
+        assert_eq!(
+            code(schedule.admit(SECOND, None)),
+            UCode::FAILED_PRECONDITION.value()
+        );
+    }
+
+    #[test]
+    fn preempting_requests_replace_the_planned_ones() {
+        let schedule = Schedule::new(CombinationMode::Queue, 2);
+        let running = schedule.admit(SECOND, None).unwrap();
+        let queued = schedule.admit(SECOND, None).unwrap();
+        let preempting = schedule
+            .admit(SECOND, Some(CombinationMode::Preempt))
+            .unwrap();
+        assert!(!preempting.is_queued());
+        assert_eq!(schedule.len(), 1);
+        assert!(preempting.start());
+        assert!(!queued.start());
+        drop(running);
+        assert_eq!(schedule.len(), 1);
+
+        schedule.clear();
+        assert!(!preempting.start());
+        assert_eq!(schedule.len(), 0);
+    }
+}
@@ -0,0 +1,106 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::time::Duration;
+
+use horn_proto::descriptors::horn;
+use horn_proto::horn_topics::HornStatus;
+use log::{debug, warn};
+use tokio::select;
+use tokio::sync::watch;
+use up_rust::communication::{CallOptions, Publisher, UPayload};
+
+use crate::actuator::ActuatorPresence;
+
+/// Passes the current status of the horn to the status publisher.
+pub(crate) type StatusSender = watch::Sender<HornStatus>;
+
+/// Creates the channel of the status, starting with an inactive horn.
+pub(crate) fn channel() -> (StatusSender, watch::Receiver<HornStatus>) {
+    watch::channel(HornStatus::default())
+}
+
+// Publishes the status on the topic `horn` (0x8000) whenever it or the presence of the
+// actuator changes, and repeats the current status every 'interval', so that subscribers
+// joining later learn the status without waiting for the next switch of the horn.
+pub(crate) async fn publish_status(
+    publisher: impl Publisher,
+    mut rx_status: watch::Receiver<HornStatus>,
+    mut actuator: ActuatorPresence,
+    interval: Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        select! {
+            changed = rx_status.changed() => if changed.is_err() {
+                return;
+            },
+            _ = actuator.changed() => {}
+            _ = ticker.tick() => {}
+        }
+        let status = reported_status(&rx_status.borrow_and_update(), actuator.is_present());
+        ticker.reset();
+        debug!("Publishing the horn status: {status}");
+        let payload = match UPayload::try_from_protobuf(status) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Failed to encode the horn status: {e}");
+                continue;
+            }
+        };
+        if let Err(e) = publisher
+            .publish(
+                horn::topics::HORN,
+                CallOptions::for_publish(None, None, None),
+                Some(payload),
+            )
+            .await
+        {
+            warn!("Failed to publish the horn status: {e}");
+        }
+    }
+}
+
+// Without the actuator, the horn can't sound whatever the request processor plays,
+// so the status reports an inactive horn with a fault.
+fn reported_status(status: &HornStatus, actuator_present: bool) -> HornStatus {
+    if actuator_present {
+        status.clone()
+    } else {
+        HornStatus {
+            is_fault_active: true,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use horn_proto::horn_topics::HornMode;
+
+    #[test]
+    fn absent_actuator_is_reported_as_fault() {
+        let playing = HornStatus {
+            is_active: true,
+            mode: HornMode::HM_CONTINUOUS.into(),
+            ..Default::default()
+        };
+        assert_eq!(reported_status(&playing, true), playing);
+
+        let reported = reported_status(&playing, false);
+        assert!(reported.is_fault_active);
+        assert!(!reported.is_active);
+        assert_eq!(reported.mode.enum_value(), Ok(HornMode::HM_UNSPECIFIED));
+    }
+}
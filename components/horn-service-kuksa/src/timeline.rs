@@ -0,0 +1,240 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::time::{Duration, SystemTime};
+
+use horn_proto::horn_service::ActivateHornRequest;
+use horn_proto::horn_topics::{HornMode, HornStatus};
+use horn_proto::status::Status;
+use protobuf::well_known_types::any::Any;
+use protobuf::well_known_types::duration::Duration as ProtoDuration;
+use protobuf::well_known_types::timestamp::Timestamp;
+
+/// A switch of the horn, relative to the start of the request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Transition {
+    pub at: Duration,
+    pub is_active: bool,
+    /// The index of the sequence and of the cycle within the sequence, for sequenced requests.
+    pub position: Option<(usize, usize)>,
+}
+
+/// The transitions of the horn an `ActivateHornRequest` results in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Timeline {
+    transitions: Vec<Transition>,
+    // None for a continuous activation, which lasts until it is deactivated
+    duration: Option<Duration>,
+    // The number of cycles of each sequence
+    cycle_counts: Vec<usize>,
+}
+
+impl Timeline {
+    /// Compiles a request into its transitions.
+    pub fn compile(request: &ActivateHornRequest) -> Result<Self, String> {
+        match request.mode.enum_value() {
+            Ok(HornMode::HM_CONTINUOUS) => Ok(Self {
+                transitions: vec![Transition {
+                    at: Duration::ZERO,
+                    is_active: true,
+                    position: None,
+                }],
+                duration: None,
+                cycle_counts: Vec::new(),
+            }),
+            Ok(HornMode::HM_SEQUENCED) => {
+                let mut transitions = Vec::new();
+                let mut at = Duration::ZERO;
+                for (sequence_index, sequence) in request.command.iter().enumerate() {
+                    for (cycle_index, cycle) in sequence.horn_cycles.iter().enumerate() {
+                        let position = Some((sequence_index, cycle_index));
+                        transitions.push(Transition {
+                            at,
+                            is_active: true,
+                            position,
+                        });
+                        at += millis(cycle.on_time);
+                        transitions.push(Transition {
+                            at,
+                            is_active: false,
+                            position,
+                        });
+                        at += millis(cycle.off_time);
+                    }
+                }
+                Ok(Self {
+                    transitions,
+                    duration: Some(at),
+                    cycle_counts: request
+                        .command
+                        .iter()
+                        .map(|sequence| sequence.horn_cycles.len())
+                        .collect(),
+                })
+            }
+            Ok(mode) => Err(format!("unsupported horn mode {mode:?}")),
+            Err(value) => Err(format!("unknown horn mode value {value}")),
+        }
+    }
+
+    pub fn transitions(&self) -> &[Transition] {
+        &self.transitions
+    }
+
+    /// The time until the request is completed, None if it lasts until it is deactivated.
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    pub fn total_sequences(&self) -> usize {
+        self.cycle_counts.len()
+    }
+
+    /// The status of the horn after a transition of the timeline. While a sequenced
+    /// request switches the horn on, the status tells the sequence counting from 1
+    /// and the cycles of the sequence following the current one.
+    pub fn status(&self, transition: &Transition) -> HornStatus {
+        if !transition.is_active {
+            return HornStatus::default();
+        }
+        let Some((sequence, cycle)) = transition.position else {
+            return HornStatus {
+                is_active: true,
+                mode: HornMode::HM_CONTINUOUS.into(),
+                ..Default::default()
+            };
+        };
+        let count = |count: usize| i32::try_from(count).unwrap_or(i32::MAX);
+        HornStatus {
+            is_active: true,
+            mode: HornMode::HM_SEQUENCED.into(),
+            current_sequence: Some(count(sequence + 1)),
+            remaining_cycles: Some(count(self.cycle_counts[sequence] - cycle - 1)),
+            total_sequences: Some(count(self.total_sequences())),
+            ..Default::default()
+        }
+    }
+
+    /// Adds the planned duration as `google.protobuf.Duration` and the planned end as
+    /// `google.protobuf.Timestamp` to the details of the status, if the request ends by itself.
+    pub fn add_plan_details(&self, status: &mut Status, start: SystemTime) {
+        let Some(duration) = self.duration else {
+            return;
+        };
+        let details = [
+            Any::pack(&ProtoDuration::from(duration)),
+            Any::pack(&Timestamp::from(start + duration)),
+        ];
+        status.details.extend(details.into_iter().flatten());
+    }
+}
+
+fn millis(time: i32) -> Duration {
+    Duration::from_millis(u64::try_from(time).unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use horn_proto::pattern;
+
+    fn timeline(notation: &str) -> Timeline {
+        Timeline::compile(&pattern::parse_request(notation).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn sequences_are_compiled_into_switches() {
+        let timeline = timeline("2x(100/50); 200/300");
+        let switches: Vec<_> = timeline
+            .transitions()
+            .iter()
+            .map(|transition| (transition.at.as_millis(), transition.is_active))
+            .collect();
+        assert_eq!(
+            switches,
+            [
+                (0, true),
+                (100, false),
+                (150, true),
+                (250, false),
+                (300, true),
+                (500, false)
+            ]
+        );
+        assert_eq!(timeline.duration(), Some(Duration::from_millis(800)));
+        assert_eq!(timeline.total_sequences(), 2);
+    }
+
+    #[test]
+    fn continuous_requests_have_no_end() {
+        let timeline = timeline("continuous");
+        assert_eq!(timeline.transitions().len(), 1);
+        assert_eq!(timeline.duration(), None);
+
+        let status = timeline.status(&timeline.transitions()[0]);
+        assert!(status.is_active);
+        assert_eq!(status.mode.enum_value(), Ok(HornMode::HM_CONTINUOUS));
+        assert_eq!(status.current_sequence, None);
+    }
+
+    #[test]
+    fn unspecified_modes_are_rejected() {
+        assert!(Timeline::compile(&ActivateHornRequest::default()).is_err());
+    }
+
+    #[test]
+    fn status_follows_the_position_in_the_sequences() {
+        let timeline = timeline("3x(100/100); 200/200");
+        let statuses: Vec<_> = timeline
+            .transitions()
+            .iter()
+            .map(|transition| timeline.status(transition))
+            .filter(|status| status.is_active)
+            .map(|status| (status.current_sequence, status.remaining_cycles))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                (Some(1), Some(2)),
+                (Some(1), Some(1)),
+                (Some(1), Some(0)),
+                (Some(2), Some(0))
+            ]
+        );
+        let last = timeline.transitions().last().unwrap();
+        assert_eq!(timeline.status(last), HornStatus::default());
+        assert_eq!(
+            timeline.status(&timeline.transitions()[0]).total_sequences,
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn plan_details_carry_duration_and_end() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let mut status = Status::new();
+        timeline("500/500").add_plan_details(&mut status, start);
+        assert_eq!(status.details.len(), 2);
+        let duration = status.details[0]
+            .unpack::<ProtoDuration>()
+            .unwrap()
+            .unwrap();
+        assert_eq!(duration.seconds, 1);
+        let end = status.details[1].unpack::<Timestamp>().unwrap().unwrap();
+        assert_eq!(end.seconds, 1_001);
+
+        let mut status = Status::new();
+        timeline("continuous").add_plan_details(&mut status, start);
+        assert!(status.details.is_empty());
+    }
+}
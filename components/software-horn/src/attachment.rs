@@ -0,0 +1,158 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use zenoh::sample::Sample;
+
+// The key of the value type in the attachment map of zenoh-pico
+const TYPE_KEY: &[u8] = b"type";
+
+/// The encoding of the attachment carrying the value type (`targetValue` or `currentValue`).
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AttachmentFormat {
+    /// Replies in the format of the last received target value, a plain string until then.
+    #[default]
+    Auto,
+    /// The value type as plain string, as used by the Zenoh-Kuksa provider.
+    String,
+    /// A bytes map with the value type under the key `type`, as used by
+    /// the actuator provider on the ESP32 with zenoh-pico.
+    Map,
+}
+
+/// Extracts the value type from the attachment of a sample together with the format it was encoded in.
+pub fn extract_value_type(sample: &Sample) -> Option<(String, AttachmentFormat)> {
+    decode_value_type(&sample.attachment()?.to_bytes())
+}
+
+fn decode_value_type(attachment: &[u8]) -> Option<(String, AttachmentFormat)> {
+    if let Some(value_type) = decode_map(attachment)
+        .and_then(|map| map.into_iter().find(|(key, _)| key == TYPE_KEY))
+        .and_then(|(_, value)| String::from_utf8(value).ok())
+    {
+        return Some((value_type, AttachmentFormat::Map));
+    }
+    String::from_utf8(attachment.to_vec())
+        .ok()
+        .map(|value_type| (value_type, AttachmentFormat::String))
+}
+
+/// Encodes the value type as attachment in the given format.
+pub fn encode_value_type(value_type: &str, format: AttachmentFormat) -> Vec<u8> {
+    match format {
+        AttachmentFormat::Map => {
+            let mut attachment = Vec::new();
+            for bytes in [TYPE_KEY, value_type.as_bytes()] {
+                write_varint(&mut attachment, bytes.len() as u64);
+                attachment.extend_from_slice(bytes);
+            }
+            attachment
+        }
+        AttachmentFormat::Auto | AttachmentFormat::String => value_type.as_bytes().to_vec(),
+    }
+}
+
+// Decodes the key-value pairs of a zenoh-pico bytes map, each key and value
+// prefixed with its length as variable-length integer. Returns `None` unless
+// the attachment consists of complete key-value pairs only.
+fn decode_map(mut bytes: &[u8]) -> Option<Vec<(Vec<u8>, Vec<u8>)>> {
+    let mut map = Vec::new();
+    while !bytes.is_empty() {
+        let key = read_slice(&mut bytes)?;
+        let value = read_slice(&mut bytes)?;
+        map.push((key.to_vec(), value.to_vec()));
+    }
+    (!map.is_empty()).then_some(map)
+}
+
+fn read_slice<'a>(bytes: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = usize::try_from(read_varint(bytes)?).ok()?;
+    if bytes.len() < len {
+        return None;
+    }
+    let (slice, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Some(slice)
+}
+
+/// Reads a variable-length integer as used by zenoh-pico and protobuf.
+pub fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn value_types_round_trip_in_both_formats() {
+        for format in [AttachmentFormat::String, AttachmentFormat::Map] {
+            let attachment = encode_value_type("targetValue", format);
+            assert_eq!(
+                decode_value_type(&attachment),
+                Some(("targetValue".to_string(), format))
+            );
+        }
+    }
+
+    #[test]
+    fn map_attachments_are_length_prefixed_pairs() {
+        assert_eq!(
+            encode_value_type("currentValue", AttachmentFormat::Map),
+            b"\x04type\x0ccurrentValue"
+        );
+        let attachment = b"\x02id\x01\x07\x04type\x0btargetValue";
+        assert_eq!(
+            decode_value_type(attachment),
+            Some(("targetValue".to_string(), AttachmentFormat::Map))
+        );
+    }
+
+    #[test]
+    fn incomplete_maps_are_read_as_strings() {
+        assert_eq!(decode_map(b"\x04type\x0ccurrent"), None);
+        assert_eq!(decode_map(b""), None);
+        assert_eq!(
+            decode_value_type(b"targetValue"),
+            Some(("targetValue".to_string(), AttachmentFormat::String))
+        );
+    }
+
+    #[test]
+    fn varints_span_multiple_bytes() {
+        for value in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value);
+            let mut slice = bytes.as_slice();
+            assert_eq!(read_varint(&mut slice), Some(value));
+            assert!(slice.is_empty());
+        }
+        assert_eq!(read_varint(&mut [0x80, 0x80].as_slice()), None);
+    }
+}
@@ -0,0 +1,127 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use crate::attachment::read_varint;
+
+// The tag of the field `value` (field number 1, wire type varint) of `google.protobuf.BoolValue`
+const BOOL_VALUE_TAG: u8 = 0x08;
+
+/// The encoding of the boolean values in the payload of the samples.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PayloadCodec {
+    /// The strings `true` and `false`, as used by the Zenoh-Kuksa provider.
+    #[default]
+    String,
+    /// A JSON boolean, or a JSON object with the boolean in the field `value`.
+    Json,
+    /// A single byte, `0` for false and any other value for true.
+    Byte,
+    /// A serialized `google.protobuf.BoolValue`.
+    Protobuf,
+}
+
+impl PayloadCodec {
+    pub fn decode(self, payload: &[u8]) -> Result<bool, String> {
+        match self {
+            PayloadCodec::String => match std::str::from_utf8(payload).map(str::trim) {
+                Ok("true") => Ok(true),
+                Ok("false") => Ok(false),
+                Ok(value) => Err(format!("'{value}' is neither 'true' nor 'false'")),
+                Err(e) => Err(format!("payload is not a string: {e}")),
+            },
+            PayloadCodec::Json => {
+                let value: serde_json::Value =
+                    serde_json::from_slice(payload).map_err(|e| format!("invalid JSON: {e}"))?;
+                value
+                    .as_bool()
+                    .or_else(|| value.get("value").and_then(serde_json::Value::as_bool))
+                    .ok_or_else(|| format!("{value} is not a JSON boolean"))
+            }
+            PayloadCodec::Byte => match payload {
+                [byte] => Ok(*byte != 0),
+                _ => Err(format!(
+                    "expected a single byte, got {} bytes",
+                    payload.len()
+                )),
+            },
+            PayloadCodec::Protobuf => decode_bool_value(payload),
+        }
+    }
+
+    pub fn encode(self, value: bool) -> Vec<u8> {
+        match self {
+            PayloadCodec::String | PayloadCodec::Json => value.to_string().into_bytes(),
+            PayloadCodec::Byte => vec![u8::from(value)],
+            // Fields with default values are not serialized in proto3
+            PayloadCodec::Protobuf if value => vec![BOOL_VALUE_TAG, 1],
+            PayloadCodec::Protobuf => Vec::new(),
+        }
+    }
+}
+
+// Decodes a `google.protobuf.BoolValue`, which is `false` if the field `value` is absent.
+// Unknown fields are rejected, as they indicate that the payload is a different message.
+fn decode_bool_value(mut bytes: &[u8]) -> Result<bool, String> {
+    let mut value = false;
+    while let Some((&tag, rest)) = bytes.split_first() {
+        if tag != BOOL_VALUE_TAG {
+            return Err(format!(
+                "unexpected field tag {tag:#04x} in google.protobuf.BoolValue"
+            ));
+        }
+        bytes = rest;
+        value = read_varint(&mut bytes)
+            .ok_or("truncated field value in google.protobuf.BoolValue")?
+            != 0;
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoded_values_are_decoded_by_every_codec() {
+        for codec in [
+            PayloadCodec::String,
+            PayloadCodec::Json,
+            PayloadCodec::Byte,
+            PayloadCodec::Protobuf,
+        ] {
+            for value in [true, false] {
+                assert_eq!(codec.decode(&codec.encode(value)), Ok(value), "{codec:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn alternative_representations_are_accepted() {
+        assert_eq!(PayloadCodec::String.decode(b" true\n"), Ok(true));
+        assert_eq!(PayloadCodec::Json.decode(br#"{"value": true}"#), Ok(true));
+        assert_eq!(PayloadCodec::Byte.decode(&[7]), Ok(true));
+        assert_eq!(
+            PayloadCodec::Protobuf.decode(&[BOOL_VALUE_TAG, 0]),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn invalid_payloads_are_rejected() {
+        assert!(PayloadCodec::String.decode(b"1").is_err());
+        assert!(PayloadCodec::Json.decode(b"\"true\"").is_err());
+        assert!(PayloadCodec::Byte.decode(&[1, 0]).is_err());
+        assert!(PayloadCodec::Protobuf.decode(&[0x12, 0]).is_err());
+        assert!(PayloadCodec::Protobuf.decode(&[BOOL_VALUE_TAG]).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::time::Duration;
+
+use clap::{CommandFactory, FromArgMatches};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FaultMode {
+    /// Follows every command and confirms it immediately.
+    #[default]
+    None,
+    /// Follows the commands only after the fault delay, switching the horn and confirming it late.
+    Delay,
+    /// Ignores the commands without switching the horn or confirming them.
+    Drop,
+    /// Keeps the horn active and reports it as active, whatever is commanded.
+    StuckOn,
+    /// Keeps the horn inactive and reports it as inactive, whatever is commanded.
+    StuckOff,
+    /// Switches the horn on and off by itself in the flap interval.
+    Flap,
+    /// Reports the fault value instead of the state of the horn.
+    Fault,
+}
+
+#[derive(clap::Args, Clone, Debug)]
+pub struct FaultArgs {
+    #[arg(long, value_enum, default_value_t, env = "FAULT_MODE")]
+    /// The way the software horn misbehaves.
+    pub fault_mode: FaultMode,
+
+    #[arg(
+        long,
+        default_value = "1",
+        env = "FAULT_EVERY",
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    /// Applies the `delay`, `drop` and `fault` modes to every N-th command only.
+    pub fault_every: u64,
+
+    #[arg(
+        long,
+        default_value = "500",
+        env = "FAULT_DELAY",
+        value_name = "MILLISECONDS"
+    )]
+    /// The delay of the switch of the horn and its confirmation in the `delay` mode.
+    pub fault_delay: u64,
+
+    #[arg(
+        long,
+        default_value = "200",
+        env = "FAULT_FLAP_INTERVAL",
+        value_name = "MILLISECONDS",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    /// The time between two changes of the horn state in the `flap` mode.
+    pub fault_flap_interval: u64,
+
+    #[arg(long, default_value = "fault", env = "FAULT_VALUE")]
+    /// The value reported as current value in the `fault` mode.
+    pub fault_value: String,
+}
+
+// Parses the fault configuration sent to the control key with the
+// same options as on the command line, e.g. `--fault-mode delay --fault-delay 1000`.
+#[derive(clap::Parser)]
+#[command(name = "faults", no_binary_name = true)]
+struct FaultControl {
+    #[command(flatten)]
+    faults: FaultArgs,
+}
+
+/// The reaction of the software horn to a command.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Reaction {
+    /// Switches the horn to the given state after the given delay and confirms it.
+    Switch(bool, Duration),
+    /// Neither switches the horn nor confirms the command.
+    Ignore,
+    /// Reports the given value as current value without switching the horn.
+    Report(String),
+}
+
+impl FaultArgs {
+    /// Parses a fault configuration received on the control key. Options which are not
+    /// given take their default values, neither the current ones nor the `FAULT_*` variables
+    /// of the environment, which only configure the faults on startup.
+    pub fn parse_control(control: &str) -> Result<Self, clap::Error> {
+        let command = FaultControl::command().mut_args(|arg| arg.env(None));
+        let mut matches = command.try_get_matches_from(control.split_whitespace())?;
+        FaultControl::from_arg_matches_mut(&mut matches).map(|control| control.faults)
+    }
+
+    pub fn flap_interval(&self) -> Duration {
+        Duration::from_millis(self.fault_flap_interval)
+    }
+
+    /// Decides how to react to the `command_number`-th command, counted from 1,
+    /// which requests the horn to be active or inactive.
+    pub fn react(&self, command_number: u64, is_active: bool) -> Reaction {
+        let applies = command_number.is_multiple_of(self.fault_every);
+        match self.fault_mode {
+            FaultMode::Delay if applies => {
+                Reaction::Switch(is_active, Duration::from_millis(self.fault_delay))
+            }
+            FaultMode::Drop if applies => Reaction::Ignore,
+            FaultMode::Fault if applies => Reaction::Report(self.fault_value.clone()),
+            FaultMode::StuckOn => Reaction::Switch(true, Duration::ZERO),
+            FaultMode::StuckOff => Reaction::Switch(false, Duration::ZERO),
+            _ => Reaction::Switch(is_active, Duration::ZERO),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn faults(control: &str) -> FaultArgs {
+        FaultArgs::parse_control(control).unwrap()
+    }
+
+    #[test]
+    fn without_faults_commands_are_followed_immediately() {
+        let faults = faults("");
+        assert_eq!(faults.fault_mode, FaultMode::None);
+        assert_eq!(
+            faults.react(1, true),
+            Reaction::Switch(true, Duration::ZERO)
+        );
+        assert_eq!(
+            faults.react(2, false),
+            Reaction::Switch(false, Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn faults_apply_to_every_nth_command() {
+        let faults = faults("--fault-mode delay --fault-delay 300 --fault-every 3");
+        let delay = Duration::from_millis(300);
+        let reactions: Vec<_> = (1..=6).map(|n| faults.react(n, true)).collect();
+        assert_eq!(
+            reactions,
+            [
+                Reaction::Switch(true, Duration::ZERO),
+                Reaction::Switch(true, Duration::ZERO),
+                Reaction::Switch(true, delay),
+                Reaction::Switch(true, Duration::ZERO),
+                Reaction::Switch(true, Duration::ZERO),
+                Reaction::Switch(true, delay),
+            ]
+        );
+    }
+
+    #[test]
+    fn drop_and_fault_modes_do_not_switch_the_horn() {
+        assert_eq!(faults("--fault-mode drop").react(1, true), Reaction::Ignore);
+        assert_eq!(
+            faults("--fault-mode fault --fault-value broken").react(1, true),
+            Reaction::Report("broken".to_string())
+        );
+    }
+
+    #[test]
+    fn stuck_modes_ignore_the_commanded_state() {
+        assert_eq!(
+            faults("--fault-mode stuck-on").react(1, false),
+            Reaction::Switch(true, Duration::ZERO)
+        );
+        assert_eq!(
+            faults("--fault-mode stuck-off").react(2, true),
+            Reaction::Switch(false, Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn control_messages_ignore_the_environment() {
+        std::env::set_var("FAULT_MODE", "drop");
+        std::env::set_var("FAULT_DELAY", "1000");
+        let faults = faults("--fault-every 2");
+        std::env::remove_var("FAULT_MODE");
+        std::env::remove_var("FAULT_DELAY");
+        assert_eq!(faults.fault_mode, FaultMode::None);
+        assert_eq!(faults.fault_delay, 500);
+        assert_eq!(faults.fault_every, 2);
+    }
+
+    #[test]
+    fn invalid_control_messages_are_rejected() {
+        assert!(FaultArgs::parse_control("--fault-mode melt").is_err());
+        assert!(FaultArgs::parse_control("--fault-every 0").is_err());
+    }
+}
@@ -14,19 +14,118 @@
 use clap::Parser;
 use env_logger::Env;
 use log::{debug, error, info, warn};
+use std::collections::VecDeque;
 use std::path::PathBuf;
-use zenoh::bytes::ZBytes;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+use zenoh::bytes::{Encoding, ZBytes};
 use zenoh::pubsub::Publisher;
-use zenoh::sample::Sample;
 use zenoh::Config;
 
+use attachment::AttachmentFormat;
+use codec::PayloadCodec;
+use faults::{FaultArgs, FaultMode, Reaction};
+use state::HornState;
+use timeline::{ExpectedSequence, Signal, Timeline};
+
+mod attachment;
+mod codec;
+mod faults;
+mod simulator;
+mod sound;
+mod state;
+mod timeline;
+
 #[derive(clap::Parser)]
 pub struct Args {
     #[arg(short, long, env = "ZENOH_CONFIG")]
     /// A Zenoh configuration file.
     config: PathBuf,
+    #[arg(
+        short,
+        long,
+        default_value = "Vehicle/Body/Horn/IsActive",
+        env = "HORN_KEY",
+        value_name = "KEY_EXPR"
+    )]
+    /// The key expression on which the target values are received.
+    key: String,
+    #[arg(long, env = "ACTUATORS_CONFIG", value_name = "PATH")]
+    /// A JSON5 file with further VSS actuators to simulate next to the horn.
+    actuators: Option<PathBuf>,
+    #[arg(long, env = "HORN_CURRENT_KEY", value_name = "KEY_EXPR")]
+    /// The key expression on which the current values are published, if it differs from the key.
+    current_key: Option<String>,
+    #[arg(long, value_enum, default_value_t, env = "PAYLOAD_CODEC")]
+    /// The encoding of the target and current values in the payload.
+    codec: PayloadCodec,
     #[arg(short, long, default_value = "true", env = "IS_SOUND_ENABLED")]
+    /// Synthesizes a horn tone while the horn is active, if a sound output is set.
     sound: bool,
+    #[arg(long, env = "SOUND_OUTPUT", value_name = "PATH")]
+    /// A WAV file to record the horn tone to, or `-` for raw mono 16-bit little-endian PCM on stdout.
+    sound_output: Option<PathBuf>,
+    #[arg(
+        long,
+        default_value = "420",
+        env = "SOUND_FREQUENCY",
+        value_name = "HZ"
+    )]
+    /// The frequency of the horn tone.
+    frequency: f32,
+    #[arg(long, default_value = "0.5", env = "SOUND_VOLUME", value_parser = parse_volume)]
+    /// The volume of the horn tone between 0.0 and 1.0.
+    volume: f32,
+    #[arg(
+        long,
+        default_value = "44100",
+        env = "SOUND_SAMPLE_RATE",
+        value_name = "HZ",
+        value_parser = clap::value_parser!(u32).range(8000..=192_000)
+    )]
+    /// The sample rate of the horn tone, between 8000 and 192000 Hz.
+    sample_rate: u32,
+    #[command(flatten)]
+    faults: FaultArgs,
+    #[arg(
+        long,
+        default_value = "software-horn/faults",
+        env = "FAULT_CONTROL_KEY",
+        value_name = "KEY_EXPR"
+    )]
+    /// The key expression on which the fault options are received at runtime,
+    /// e.g. `--fault-mode delay --fault-delay 1000`.
+    control_key: String,
+    #[arg(long, env = "TIMELINE_CSV", value_name = "PATH")]
+    /// A CSV file to record the transitions of the target value and of the horn to.
+    timeline_csv: Option<PathBuf>,
+    #[arg(long, env = "TIMELINE_VCD", value_name = "PATH")]
+    /// A VCD file to record the transitions of the target value and of the horn to.
+    timeline_vcd: Option<PathBuf>,
+    #[arg(long = "expect", value_parser = timeline::parse_expected_sequence, value_name = "ON/OFF ...")]
+    /// The expected horn cycles given as on and off times in milliseconds, e.g. `100/100,200/300`
+    /// or `3x(100/100) 500/30`, to compare the recorded horn cycles with on shutdown.
+    /// Repeat the option for multiple sequences.
+    expected: Vec<ExpectedSequence>,
+    #[arg(
+        long,
+        default_value = "20",
+        env = "TIMELINE_TOLERANCE",
+        value_name = "MILLISECONDS"
+    )]
+    /// The accepted deviation of the recorded from the expected on and off times.
+    tolerance: u64,
+    #[arg(long, value_enum, default_value_t, env = "ATTACHMENT_FORMAT")]
+    /// The encoding of the value type in the attachment of the published current values.
+    attachment_format: AttachmentFormat,
+}
+
+fn parse_volume(volume: &str) -> Result<f32, String> {
+    match volume.parse::<f32>() {
+        Ok(volume) if (0.0..=1.0).contains(&volume) => Ok(volume),
+        Ok(volume) => Err(format!("volume {volume} is not between 0.0 and 1.0")),
+        Err(e) => Err(e.to_string()),
+    }
 }
 
 impl Args {
@@ -43,7 +142,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let zenoh_config = args.get_zenoh_config()?;
     info!("Starting the software horn connected over Eclipse Zenoh");
 
-    let horn_keyexpr = String::from("Vehicle/Body/Horn/IsActive");
+    let tx_sound = match &args.sound_output {
+        Some(output) if args.sound => Some(sound::start(
+            output,
+            sound::ToneConfig {
+                frequency: args.frequency,
+                volume: args.volume,
+                sample_rate: args.sample_rate,
+            },
+        )?),
+        _ => None,
+    };
+
+    let horn_keyexpr = args.key.clone();
+    let current_keyexpr = args.current_key.clone().unwrap_or_else(|| args.key.clone());
+    let codec = args.codec;
 
     let session = zenoh::open(zenoh_config)
         .await
@@ -55,49 +168,215 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .map_err(|e| e as Box<dyn std::error::Error>)?;
 
     let publisher = session
-        .declare_publisher(&horn_keyexpr)
+        .declare_publisher(&current_keyexpr)
         .await
         .map_err(|e| e as Box<dyn std::error::Error>)?;
-    debug!("Waiting for messages on topic: {}", &horn_keyexpr);
+    // Announces the presence of the horn as long as the software horn is running
+    let _liveliness_token = session
+        .liveliness()
+        .declare_token(&horn_keyexpr)
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?;
+    // Answers late joiners with the state of the horn in the configured codec
+    let queryable = session
+        .declare_queryable(&current_keyexpr)
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?;
+    let stats_keyexpr = format!("{current_keyexpr}/stats");
+    let stats_queryable = session
+        .declare_queryable(&stats_keyexpr)
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?;
+    let control_subscriber = session
+        .declare_subscriber(&args.control_key)
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?;
+    if let Some(actuators) = &args.actuators {
+        for actuator in simulator::load_actuators(actuators)? {
+            if actuator.keyexpr() == horn_keyexpr {
+                return Err(format!(
+                    "the actuator {} is already simulated by the horn",
+                    actuator.path
+                )
+                .into());
+            }
+            let simulator =
+                simulator::Simulator::declare(&session, actuator, args.attachment_format).await?;
+            tokio::spawn(simulator.run());
+        }
+    }
+    debug!(
+        "Waiting for {codec:?} encoded messages on topic: {}",
+        &horn_keyexpr
+    );
 
-    while let Ok(sample) = subscriber.recv_async().await {
-        if let Some(value_type) = extract_attachment_as_string(&sample) {
-            if value_type == "targetValue" {
-                match zbytes_to_string(sample.payload()) {
+    let mut timeline = Timeline::new(args.timeline_csv.as_deref(), args.timeline_vcd.as_deref())?;
+    let mut faults = args.faults.clone();
+    if faults.fault_mode != FaultMode::None {
+        info!("Injecting faults: {:?}", faults);
+    }
+    let mut reply_format = match args.attachment_format {
+        AttachmentFormat::Auto => AttachmentFormat::String,
+        format => format,
+    };
+    let mut command_number = 0u64;
+    let mut state = HornState::default();
+    pub_current_status(&publisher, state.is_active(), codec, reply_format).await;
+    let mut flap_ticker = tokio::time::interval(faults.flap_interval());
+    // The switches of the horn held back in the `delay` mode, ordered by the time they are due
+    let mut delayed: VecDeque<(tokio::time::Instant, bool)> = VecDeque::new();
+    loop {
+        tokio::select! {
+            sample = subscriber.recv_async() => {
+                let Ok(sample) = sample else { break };
+                match attachment::extract_value_type(&sample) {
+                    Some((value_type, format)) if value_type == "targetValue" => {
+                        if args.attachment_format == AttachmentFormat::Auto && format != reply_format {
+                            info!("Replying with attachments in the {format:?} format");
+                            reply_format = format;
+                        }
+                    }
+                    _ => continue,
+                }
+                match codec.decode(&sample.payload().to_bytes()) {
                     Ok(value) => {
-                        if value == "true" {
-                            info!("activate Horn");
-                            pub_current_status(&publisher, true).await;
-                        } else {
-                            info!("deactivate Horn");
-                            pub_current_status(&publisher, false).await;
+                        timeline.record(Signal::Target, value, Instant::now());
+                        command_number += 1;
+                        match faults.react(command_number, value) {
+                            Reaction::Switch(is_active, delay) if delay.is_zero() => {
+                                switch_horn(is_active, &tx_sound, &mut timeline, &mut state);
+                                pub_current_status(&publisher, is_active, codec, reply_format).await;
+                            }
+                            Reaction::Switch(is_active, delay) => {
+                                let due = tokio::time::Instant::now() + delay;
+                                let index = delayed.partition_point(|(other, _)| *other <= due);
+                                delayed.insert(index, (due, is_active));
+                            }
+                            Reaction::Ignore => warn!("Dropping the command {value}"),
+                            Reaction::Report(fault_value) => {
+                                warn!("Reporting the fault value {fault_value} for the command {value}");
+                                pub_current_value(&publisher, fault_value, reply_format).await;
+                            }
                         }
                     }
-                    Err(e) => error!("Payload from Zenoh message is not a String: {e}"),
+                    Err(e) => error!("Payload from Zenoh message is not a {codec:?} encoded boolean: {e}"),
                 }
             }
+            control = control_subscriber.recv_async() => {
+                let Ok(control) = control else { break };
+                let new_faults = zbytes_to_string(control.payload())
+                    .map_err(|e| e.to_string())
+                    .and_then(|control| FaultArgs::parse_control(&control).map_err(|e| e.to_string()));
+                match new_faults {
+                    Ok(new_faults) => {
+                        info!("Switching the fault injection to {:?}", new_faults);
+                        faults = new_faults;
+                        flap_ticker = tokio::time::interval(faults.flap_interval());
+                        match faults.fault_mode {
+                            FaultMode::StuckOn | FaultMode::StuckOff => {
+                                delayed.clear();
+                                let is_active = faults.fault_mode == FaultMode::StuckOn;
+                                switch_horn(is_active, &tx_sound, &mut timeline, &mut state);
+                                pub_current_status(&publisher, is_active, codec, reply_format).await;
+                            }
+                            _ => {}
+                        }
+                    }
+                    Err(e) => warn!("Ignoring the invalid fault options: {e}"),
+                }
+            }
+            _ = tokio::time::sleep_until(delayed.front().map_or_else(tokio::time::Instant::now, |(due, _)| *due)), if !delayed.is_empty() => {
+                let Some((_, is_active)) = delayed.pop_front() else { continue };
+                switch_horn(is_active, &tx_sound, &mut timeline, &mut state);
+                pub_current_status(&publisher, is_active, codec, reply_format).await;
+            }
+            _ = flap_ticker.tick(), if faults.fault_mode == FaultMode::Flap => {
+                let is_active = !state.is_active();
+                switch_horn(is_active, &tx_sound, &mut timeline, &mut state);
+                pub_current_status(&publisher, is_active, codec, reply_format).await;
+            }
+            query = queryable.recv_async() => {
+                let Ok(query) = query else { break };
+                debug!("Replying the horn state to the query {}", query.selector());
+                if let Err(e) = query
+                    .reply(&current_keyexpr, codec.encode(state.is_active()))
+                    .attachment(attachment::encode_value_type("currentValue", reply_format))
+                    .await
+                {
+                    warn!("failed to reply to the query {}: {e}", query.selector());
+                }
+            }
+            query = stats_queryable.recv_async() => {
+                let Ok(query) = query else { break };
+                debug!("Replying the horn statistics to the query {}", query.selector());
+                if let Err(e) = query
+                    .reply(&stats_keyexpr, state.to_json(Instant::now()).to_string())
+                    .encoding(Encoding::APPLICATION_JSON)
+                    .await
+                {
+                    warn!("failed to reply to the query {}: {e}", query.selector());
+                }
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("Stopping the software horn");
+                break;
+            }
         }
     }
 
+    let expected: Vec<_> = args
+        .expected
+        .iter()
+        .flat_map(|sequence| sequence.cycles.iter().copied())
+        .collect();
+    if !timeline.log_summary(&expected, Duration::from_millis(args.tolerance)) {
+        return Err("the recorded horn cycles deviate from the expected horn cycles".into());
+    }
     Ok(())
 }
 
-pub async fn pub_current_status(publisher: &Publisher<'_>, status: bool) {
+fn switch_horn(
+    is_active: bool,
+    tx_sound: &Option<Sender<(Instant, bool)>>,
+    timeline: &mut Timeline,
+    state: &mut HornState,
+) {
+    if is_active {
+        info!("activate Horn");
+    } else {
+        info!("deactivate Horn");
+    }
+    let now = Instant::now();
+    timeline.record(Signal::Horn, is_active, now);
+    state.set(is_active, now);
+    if let Some(tx_sound) = tx_sound {
+        let _ = tx_sound.send((now, is_active));
+    }
+}
+
+pub async fn pub_current_status(
+    publisher: &Publisher<'_>,
+    status: bool,
+    codec: PayloadCodec,
+    format: AttachmentFormat,
+) {
+    pub_current_value(publisher, codec.encode(status), format).await
+}
+
+pub async fn pub_current_value(
+    publisher: &Publisher<'_>,
+    value: impl Into<ZBytes>,
+    format: AttachmentFormat,
+) {
     if let Err(e) = publisher
-        .put(status.to_string())
-        .attachment("currentValue")
+        .put(value)
+        .attachment(attachment::encode_value_type("currentValue", format))
         .await
     {
         warn!("failed to publish current status: {e}");
     }
 }
 
-pub fn extract_attachment_as_string(sample: &Sample) -> Option<String> {
-    sample
-        .attachment()
-        .and_then(|a| a.try_to_string().map(|v| v.to_string()).ok())
-}
-
 pub fn zbytes_to_string(zbuf: &ZBytes) -> Result<String, std::str::Utf8Error> {
     zbuf.try_to_string().map(|v| v.to_string())
 }
@@ -0,0 +1,363 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::path::Path;
+use std::time::Duration;
+
+use log::{info, warn};
+use serde::Deserialize;
+use zenoh::handlers::FifoChannelHandler;
+use zenoh::liveliness::LivelinessToken;
+use zenoh::pubsub::Subscriber;
+use zenoh::sample::Sample;
+use zenoh::Session;
+
+use crate::attachment::{self, AttachmentFormat};
+
+/// The VSS datatypes of the simulated actuators.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Datatype {
+    Boolean,
+    String,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Uint8,
+    Uint16,
+    Uint32,
+    Uint64,
+    Float,
+    Double,
+}
+
+impl Datatype {
+    /// Checks that the value is of this datatype and returns it in its canonical form,
+    /// e.g. `1` for the float `1.0`, so that values can be compared as strings.
+    pub fn normalize(self, value: &str) -> Result<String, String> {
+        fn parse<T: std::str::FromStr + ToString>(value: &str) -> Result<String, String>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value
+                .trim()
+                .parse::<T>()
+                .map(|value| value.to_string())
+                .map_err(|e| e.to_string())
+        }
+        match self {
+            Datatype::Boolean => parse::<bool>(value),
+            Datatype::String => Ok(value.to_string()),
+            Datatype::Int8 => parse::<i8>(value),
+            Datatype::Int16 => parse::<i16>(value),
+            Datatype::Int32 => parse::<i32>(value),
+            Datatype::Int64 => parse::<i64>(value),
+            Datatype::Uint8 => parse::<u8>(value),
+            Datatype::Uint16 => parse::<u16>(value),
+            Datatype::Uint32 => parse::<u32>(value),
+            Datatype::Uint64 => parse::<u64>(value),
+            Datatype::Float => parse::<f32>(value),
+            Datatype::Double => parse::<f64>(value),
+        }
+        .map_err(|e| format!("'{value}' is not a valid {self:?} value: {e}"))
+    }
+}
+
+// A value as given in the actuator file, where numbers and booleans may be written without quotes.
+// Integers are kept exact, since a float only holds integers up to 2^53.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum ConfigValue {
+    Boolean(bool),
+    Integer(i64),
+    Number(f64),
+    String(String),
+}
+
+impl ConfigValue {
+    fn to_value_string(&self) -> String {
+        match self {
+            ConfigValue::Boolean(value) => value.to_string(),
+            ConfigValue::Integer(value) => value.to_string(),
+            ConfigValue::Number(value) => value.to_string(),
+            ConfigValue::String(value) => value.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ActuatorConfig {
+    path: String,
+    datatype: Datatype,
+    #[serde(default)]
+    allowed: Vec<ConfigValue>,
+    #[serde(default)]
+    latency: u64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ActuatorsFile {
+    actuators: Vec<ActuatorConfig>,
+}
+
+/// A simulated VSS actuator.
+#[derive(Clone, Debug)]
+pub struct Actuator {
+    /// The VSS path, e.g. `Vehicle.Body.Lights.Hazard.IsSignaling`.
+    pub path: String,
+    pub datatype: Datatype,
+    /// The values the actuator accepts in canonical form. Any value of the datatype is accepted, if empty.
+    pub allowed: Vec<String>,
+    /// The time from receiving a target value until publishing it as current value.
+    pub latency: Duration,
+}
+
+impl Actuator {
+    /// The key expression of the actuator, as used by the Zenoh-Kuksa provider.
+    pub fn keyexpr(&self) -> String {
+        self.path.replace('.', "/")
+    }
+
+    fn validate(&self, value: &str) -> Result<String, String> {
+        let value = self.datatype.normalize(value)?;
+        if !self.allowed.is_empty() && !self.allowed.contains(&value) {
+            return Err(format!(
+                "'{value}' is not one of the allowed values {:?}",
+                self.allowed
+            ));
+        }
+        Ok(value)
+    }
+}
+
+/// Reads the actuators to simulate from a JSON5 file.
+pub fn load_actuators(path: &Path) -> Result<Vec<Actuator>, Box<dyn std::error::Error>> {
+    let file: ActuatorsFile = json5::from_str(&std::fs::read_to_string(path)?)
+        .map_err(|e| format!("invalid actuator file {}: {e}", path.display()))?;
+    file.actuators
+        .into_iter()
+        .map(|config| {
+            let allowed = config
+                .allowed
+                .iter()
+                .map(|value| config.datatype.normalize(&value.to_value_string()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("actuator {}: {e}", config.path))?;
+            Ok(Actuator {
+                path: config.path,
+                datatype: config.datatype,
+                allowed,
+                latency: Duration::from_millis(config.latency),
+            })
+        })
+        .collect()
+}
+
+/// Serves a simulated actuator: every valid target value is published as
+/// current value after the latency of the actuator, invalid ones are ignored.
+pub struct Simulator {
+    actuator: Actuator,
+    session: Session,
+    subscriber: Subscriber<FifoChannelHandler<Sample>>,
+    _liveliness_token: LivelinessToken,
+    attachment_format: AttachmentFormat,
+}
+
+impl Simulator {
+    pub async fn declare(
+        session: &Session,
+        actuator: Actuator,
+        attachment_format: AttachmentFormat,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let keyexpr = actuator.keyexpr();
+        let subscriber = session
+            .declare_subscriber(&keyexpr)
+            .await
+            .map_err(|e| e as Box<dyn std::error::Error>)?;
+        let liveliness_token = session
+            .liveliness()
+            .declare_token(&keyexpr)
+            .await
+            .map_err(|e| e as Box<dyn std::error::Error>)?;
+        info!(
+            "Simulating the {:?} actuator {} at [{keyexpr}]",
+            actuator.datatype, actuator.path
+        );
+        Ok(Self {
+            actuator,
+            session: session.clone(),
+            subscriber,
+            _liveliness_token: liveliness_token,
+            attachment_format,
+        })
+    }
+
+    pub async fn run(self) {
+        let keyexpr = self.actuator.keyexpr();
+        let mut reply_format = match self.attachment_format {
+            AttachmentFormat::Auto => AttachmentFormat::String,
+            format => format,
+        };
+        while let Ok(sample) = self.subscriber.recv_async().await {
+            match attachment::extract_value_type(&sample) {
+                Some((value_type, format)) if value_type == "targetValue" => {
+                    if self.attachment_format == AttachmentFormat::Auto {
+                        reply_format = format;
+                    }
+                }
+                _ => continue,
+            }
+            let value = match sample.payload().try_to_string() {
+                Ok(value) => value.to_string(),
+                Err(e) => {
+                    warn!("{}: payload is not a string: {e}", self.actuator.path);
+                    continue;
+                }
+            };
+            let value = match self.actuator.validate(&value) {
+                Ok(value) => value,
+                Err(e) => {
+                    warn!("{}: ignoring the target value: {e}", self.actuator.path);
+                    continue;
+                }
+            };
+            info!("{}: switching to '{value}'", self.actuator.path);
+
+            // Publishes the current value after the latency without blocking further target values
+            let session = self.session.clone();
+            let keyexpr = keyexpr.clone();
+            let latency = self.actuator.latency;
+            tokio::spawn(async move {
+                tokio::time::sleep(latency).await;
+                if let Err(e) = session
+                    .put(&keyexpr, value)
+                    .attachment(attachment::encode_value_type("currentValue", reply_format))
+                    .await
+                {
+                    warn!("failed to publish current value of {keyexpr}: {e}");
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(content: &str) -> Result<Vec<Actuator>, String> {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, content.as_bytes()).unwrap();
+        load_actuators(file.path()).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn values_are_normalized_to_their_canonical_form() {
+        assert_eq!(
+            Datatype::Boolean.normalize(" true "),
+            Ok("true".to_string())
+        );
+        assert!(Datatype::Boolean.normalize("1").is_err());
+        assert_eq!(Datatype::Int8.normalize("-128"), Ok("-128".to_string()));
+        assert!(Datatype::Int8.normalize("128").is_err());
+        assert!(Datatype::Uint8.normalize("-1").is_err());
+        assert_eq!(
+            Datatype::Uint64.normalize("18446744073709551615"),
+            Ok("18446744073709551615".to_string())
+        );
+        assert_eq!(Datatype::Float.normalize("1.0"), Ok("1".to_string()));
+        assert_eq!(Datatype::Double.normalize("1.50"), Ok("1.5".to_string()));
+        assert!(Datatype::Double.normalize("fast").is_err());
+        assert_eq!(Datatype::String.normalize(" OFF"), Ok(" OFF".to_string()));
+    }
+
+    #[test]
+    fn actuators_accept_only_allowed_values() {
+        let actuator = Actuator {
+            path: "Vehicle.Body.Horn.Level".to_string(),
+            datatype: Datatype::Float,
+            allowed: vec!["0.5".to_string(), "1".to_string()],
+            latency: Duration::ZERO,
+        };
+        assert_eq!(actuator.keyexpr(), "Vehicle/Body/Horn/Level");
+        assert_eq!(actuator.validate("1.0"), Ok("1".to_string()));
+        assert_eq!(actuator.validate(".5"), Ok("0.5".to_string()));
+        assert!(actuator.validate("0.75").is_err());
+
+        let any = Actuator {
+            allowed: Vec::new(),
+            ..actuator
+        };
+        assert_eq!(any.validate("0.75"), Ok("0.75".to_string()));
+    }
+
+    #[test]
+    fn actuators_are_loaded_from_json5() {
+        let actuators = load(
+            r#"{
+                // comments and unquoted keys are allowed
+                actuators: [
+                    { path: "Vehicle.Body.Windshield.Front.Wiping.Mode", datatype: "string", allowed: ["OFF", "FAST"] },
+                    { path: "Vehicle.Body.Lights.Hazard.IsSignaling", datatype: "boolean", allowed: [true], latency: 50 },
+                    { path: "Vehicle.Cabin.Light.Brightness", datatype: "float", allowed: [1.0, 0.25] },
+                ],
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(actuators.len(), 3);
+        assert_eq!(actuators[0].allowed, ["OFF", "FAST"]);
+        assert_eq!(actuators[1].allowed, ["true"]);
+        assert_eq!(actuators[1].latency, Duration::from_millis(50));
+        assert_eq!(actuators[2].allowed, ["1", "0.25"]);
+    }
+
+    #[test]
+    fn large_integers_keep_their_precision() {
+        let actuators = load(
+            r#"{ actuators: [
+                { path: "A.Uint", datatype: "uint64", allowed: [9007199254740993, "18446744073709551615"] },
+                { path: "A.Int", datatype: "int64", allowed: [-9007199254740993, 9223372036854775807] },
+            ] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            actuators[0].allowed,
+            ["9007199254740993", "18446744073709551615"]
+        );
+        assert_eq!(
+            actuators[1].allowed,
+            ["-9007199254740993", "9223372036854775807"]
+        );
+    }
+
+    #[test]
+    fn invalid_actuator_files_are_rejected() {
+        let error = load(r#"{ actuators: [{ path: "A.B", datatype: "boolean", unit: "km" }] }"#)
+            .unwrap_err();
+        assert!(error.contains("unknown field `unit`"), "{error}");
+
+        let error = load(r#"{ actuators: [{ path: "A.B", datatype: "decimal" }] }"#).unwrap_err();
+        assert!(error.contains("decimal"), "{error}");
+
+        let error =
+            load(r#"{ actuators: [{ path: "A.B", datatype: "uint8", allowed: [1, 256] }] }"#)
+                .unwrap_err();
+        assert!(
+            error.contains("actuator A.B: '256' is not a valid Uint8 value"),
+            "{error}"
+        );
+    }
+}
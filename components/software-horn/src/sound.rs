@@ -0,0 +1,269 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::collections::VecDeque;
+use std::f32::consts::TAU;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+
+// The interval in which the synthesized samples are written to the output
+const BLOCK_INTERVAL: Duration = Duration::from_millis(20);
+// Fade in and out over 5 ms to avoid clicks when the horn is switched
+const RAMP_SECONDS: f32 = 0.005;
+const WAV_HEADER_LEN: u32 = 44;
+const BYTES_PER_SAMPLE: u16 = 2;
+// The largest data chunk in whole samples whose length still fits into the RIFF chunk size
+const MAX_DATA_LEN: u32 =
+    (u32::MAX - (WAV_HEADER_LEN - 8)) / BYTES_PER_SAMPLE as u32 * BYTES_PER_SAMPLE as u32;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ToneConfig {
+    /// The frequency of the horn tone in Hz.
+    pub frequency: f32,
+    /// The volume of the horn tone between 0.0 and 1.0.
+    pub volume: f32,
+    /// The number of samples per second.
+    pub sample_rate: u32,
+}
+
+enum Sink {
+    /// A WAV file with mono 16-bit PCM, whose header is kept up to date,
+    /// so that the file can be played even if the software horn is killed.
+    Wav {
+        writer: BufWriter<File>,
+        data_len: u32,
+    },
+    /// Raw mono 16-bit little-endian PCM on stdout.
+    Pcm(std::io::Stdout),
+}
+
+impl Sink {
+    fn open(output: &Path, sample_rate: u32) -> std::io::Result<Self> {
+        if output == Path::new("-") {
+            return Ok(Sink::Pcm(std::io::stdout()));
+        }
+        let mut writer = BufWriter::new(File::create(output)?);
+        write_wav_header(&mut writer, sample_rate, 0)?;
+        Ok(Sink::Wav {
+            writer,
+            data_len: 0,
+        })
+    }
+
+    /// Writes the samples and returns whether the sink accepts further samples.
+    fn write(&mut self, samples: &[i16]) -> std::io::Result<bool> {
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        match self {
+            Sink::Wav { writer, data_len } => {
+                let accepted = accepted_len(*data_len, bytes.len());
+                writer.write_all(&bytes[..accepted])?;
+                *data_len += accepted as u32;
+                // Update the lengths in the RIFF and data chunk headers
+                writer.seek(SeekFrom::Start(4))?;
+                writer.write_all(&(WAV_HEADER_LEN - 8 + *data_len).to_le_bytes())?;
+                writer.seek(SeekFrom::Start(u64::from(WAV_HEADER_LEN) - 4))?;
+                writer.write_all(&data_len.to_le_bytes())?;
+                writer.seek(SeekFrom::End(0))?;
+                writer.flush()?;
+                if *data_len == MAX_DATA_LEN {
+                    warn!("Stopping the recording of the horn tone since the WAV file reached its maximum size");
+                    return Ok(false);
+                }
+                Ok(true)
+            }
+            Sink::Pcm(stdout) => {
+                let mut stdout = stdout.lock();
+                stdout.write_all(&bytes)?;
+                stdout.flush()?;
+                Ok(true)
+            }
+        }
+    }
+}
+
+// The number of bytes which can be appended to a data chunk of the given length.
+fn accepted_len(data_len: u32, len: usize) -> usize {
+    len.min((MAX_DATA_LEN - data_len) as usize)
+}
+
+fn write_wav_header(
+    writer: &mut impl Write,
+    sample_rate: u32,
+    data_len: u32,
+) -> std::io::Result<()> {
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(WAV_HEADER_LEN - 8 + data_len).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    // PCM format with a single channel
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * u32::from(BYTES_PER_SAMPLE)).to_le_bytes())?;
+    writer.write_all(&BYTES_PER_SAMPLE.to_le_bytes())?;
+    writer.write_all(&(BYTES_PER_SAMPLE * 8).to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())
+}
+
+/// Generates the samples of the horn tone, switching it on and off at the sample
+/// corresponding to the point in time at which the state of the horn changed.
+struct Synthesizer {
+    config: ToneConfig,
+    start: Instant,
+    written: u64,
+    active: bool,
+    amplitude: f32,
+    phase: f32,
+}
+
+impl Synthesizer {
+    fn sample_at(&self, instant: Instant) -> u64 {
+        (instant.saturating_duration_since(self.start).as_secs_f64()
+            * f64::from(self.config.sample_rate)) as u64
+    }
+
+    fn render(&mut self, until: Instant, changes: &mut VecDeque<(Instant, bool)>) -> Vec<i16> {
+        let end = self.sample_at(until);
+        let ramp_step = 1.0 / (RAMP_SECONDS * self.config.sample_rate as f32);
+        let phase_step = TAU * self.config.frequency / self.config.sample_rate as f32;
+        let mut samples = Vec::with_capacity(end.saturating_sub(self.written) as usize);
+        while self.written < end {
+            while let Some((_, active)) = changes
+                .front()
+                .filter(|(instant, _)| self.sample_at(*instant) <= self.written)
+            {
+                self.active = *active;
+                changes.pop_front();
+            }
+            let target = if self.active { 1.0 } else { 0.0 };
+            self.amplitude = if self.amplitude < target {
+                (self.amplitude + ramp_step).min(target)
+            } else {
+                (self.amplitude - ramp_step).max(target)
+            };
+            let value = self.config.volume * self.amplitude * self.phase.sin();
+            samples.push((value * f32::from(i16::MAX)) as i16);
+            self.phase = (self.phase + phase_step) % TAU;
+            self.written += 1;
+        }
+        samples
+    }
+}
+
+/// Starts a thread writing the horn tone to a WAV file, or raw PCM on stdout if
+/// `output` is `-`, and returns the channel to report changes of the horn state.
+/// The recording covers the time since the start, with silence while the horn is inactive.
+pub fn start(output: &Path, config: ToneConfig) -> std::io::Result<Sender<(Instant, bool)>> {
+    let mut sink = Sink::open(output, config.sample_rate)?;
+    info!(
+        "Writing a horn tone of {} Hz at volume {} to {}",
+        config.frequency,
+        config.volume,
+        output.display()
+    );
+    let (tx_state, rx_state) = mpsc::channel();
+    let synthesizer = Synthesizer {
+        config,
+        start: Instant::now(),
+        written: 0,
+        active: false,
+        amplitude: 0.0,
+        phase: 0.0,
+    };
+    std::thread::spawn(move || {
+        if let Err(e) = synthesize(synthesizer, &mut sink, rx_state) {
+            error!("Failed to write the horn tone: {e}");
+        }
+    });
+    Ok(tx_state)
+}
+
+fn synthesize(
+    mut synthesizer: Synthesizer,
+    sink: &mut Sink,
+    rx_state: Receiver<(Instant, bool)>,
+) -> std::io::Result<()> {
+    let mut changes = VecDeque::new();
+    loop {
+        std::thread::sleep(BLOCK_INTERVAL);
+        let disconnected = loop {
+            match rx_state.try_recv() {
+                Ok(change) => changes.push_back(change),
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+        let accepts_samples = sink.write(&synthesizer.render(Instant::now(), &mut changes))?;
+        if disconnected || !accepts_samples {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn synthesizer(start: Instant) -> Synthesizer {
+        Synthesizer {
+            config: ToneConfig {
+                frequency: 420.0,
+                volume: 1.0,
+                sample_rate: 8000,
+            },
+            start,
+            written: 0,
+            active: false,
+            amplitude: 0.0,
+            phase: 0.0,
+        }
+    }
+
+    #[test]
+    fn header_declares_mono_16_bit_pcm() {
+        let mut header = Vec::new();
+        write_wav_header(&mut header, 8000, 100).unwrap();
+        assert_eq!(header.len(), WAV_HEADER_LEN as usize);
+        assert_eq!(&header[0..4], b"RIFF");
+        assert_eq!(header[4..8], (WAV_HEADER_LEN - 8 + 100).to_le_bytes());
+        assert_eq!(header[24..28], 8000u32.to_le_bytes());
+        assert_eq!(header[28..32], 16000u32.to_le_bytes());
+        assert_eq!(header[40..44], 100u32.to_le_bytes());
+    }
+
+    #[test]
+    fn data_chunk_stops_at_the_riff_limit() {
+        assert_eq!(accepted_len(0, 320), 320);
+        assert_eq!(accepted_len(MAX_DATA_LEN - 100, 320), 100);
+        assert_eq!(accepted_len(MAX_DATA_LEN, 320), 0);
+    }
+
+    #[test]
+    fn tone_starts_at_the_sample_of_the_switch() {
+        let start = Instant::now();
+        let mut synthesizer = synthesizer(start);
+        let mut changes = VecDeque::from([(start + Duration::from_millis(10), true)]);
+        let samples = synthesizer.render(start + Duration::from_millis(20), &mut changes);
+        assert_eq!(samples.len(), 160);
+        assert!(samples[..80].iter().all(|sample| *sample == 0));
+        assert!(samples[80..].iter().any(|sample| *sample != 0));
+        assert!(changes.is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The state of the horn together with statistics for late joiners querying it.
+#[derive(Debug, Default)]
+pub struct HornState {
+    is_active: bool,
+    activation_count: u64,
+    // The on time of the completed activations
+    on_time: Duration,
+    // The point in time of the last activation, while the horn is active
+    active_since: Option<Instant>,
+    last_change: Option<SystemTime>,
+}
+
+impl HornState {
+    pub fn is_active(&self) -> bool {
+        self.is_active
+    }
+
+    /// Switches the horn to the given state at the given point in time, if it changed.
+    pub fn set(&mut self, is_active: bool, at: Instant) {
+        if self.is_active == is_active {
+            return;
+        }
+        self.is_active = is_active;
+        self.last_change = Some(SystemTime::now());
+        if is_active {
+            self.activation_count += 1;
+            self.active_since = Some(at);
+        } else if let Some(active_since) = self.active_since.take() {
+            self.on_time += at.saturating_duration_since(active_since);
+        }
+    }
+
+    /// The cumulative on time including the running activation up to `now`.
+    pub fn on_time(&self, now: Instant) -> Duration {
+        self.on_time
+            + self
+                .active_since
+                .map(|active_since| now.saturating_duration_since(active_since))
+                .unwrap_or_default()
+    }
+
+    /// The state and the statistics at `now` as JSON, as replied to queries.
+    pub fn to_json(&self, now: Instant) -> serde_json::Value {
+        let last_change = self.last_change.map(|last_change| {
+            last_change
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64
+        });
+        serde_json::json!({
+            "isActive": self.is_active,
+            "activationCount": self.activation_count,
+            "onTimeMs": self.on_time(now).as_millis() as u64,
+            "lastChangeUnixMs": last_change,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn on_time_accumulates_the_activations() {
+        let start = Instant::now();
+        let mut state = HornState::default();
+        assert_eq!(state.on_time(start), Duration::ZERO);
+
+        state.set(true, start);
+        assert!(state.is_active());
+        assert_eq!(state.on_time(start + ms(100)), ms(100));
+        // repeated commands neither restart nor count the activation
+        state.set(true, start + ms(50));
+        state.set(false, start + ms(200));
+        state.set(false, start + ms(300));
+        assert_eq!(state.on_time(start + ms(1000)), ms(200));
+
+        state.set(true, start + ms(1000));
+        assert_eq!(state.on_time(start + ms(1250)), ms(450));
+        state.set(false, start + ms(1300));
+        assert!(!state.is_active());
+        assert_eq!(state.on_time(start + ms(5000)), ms(500));
+        assert_eq!(state.activation_count, 2);
+    }
+
+    #[test]
+    fn statistics_are_reported_as_json() {
+        let start = Instant::now();
+        let mut state = HornState::default();
+        assert_eq!(
+            state.to_json(start),
+            serde_json::json!({
+                "isActive": false,
+                "activationCount": 0,
+                "onTimeMs": 0,
+                "lastChangeUnixMs": null,
+            })
+        );
+
+        let before = SystemTime::now();
+        state.set(true, start);
+        state.set(true, start + ms(100));
+        let json = state.to_json(start + ms(150));
+        assert_eq!(json["isActive"], true);
+        assert_eq!(json["activationCount"], 1);
+        assert_eq!(json["onTimeMs"], 150);
+        let last_change = json["lastChangeUnixMs"].as_u64().unwrap();
+        assert!(last_change >= before.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64);
+    }
+}
@@ -0,0 +1,391 @@
+/*******************************************************************************
+* Copyright (c) 2024 Contributors to the Eclipse Foundation
+*
+* See the NOTICE file(s) distributed with this work for additional
+* information regarding copyright ownership.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0
+*
+* SPDX-License-Identifier: EPL-2.0
+*******************************************************************************/
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use horn_proto::pattern;
+use log::{info, warn};
+
+/// The signals recorded in the timeline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signal {
+    /// The target value commanded by the provider.
+    Target,
+    /// The state of the software horn.
+    Horn,
+}
+
+impl Signal {
+    fn name(self) -> &'static str {
+        match self {
+            Signal::Target => "target",
+            Signal::Horn => "horn",
+        }
+    }
+
+    fn vcd_identifier(self) -> char {
+        match self {
+            Signal::Target => 't',
+            Signal::Horn => 'h',
+        }
+    }
+}
+
+/// An expected horn cycle in milliseconds, as played by the Horn service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExpectedCycle {
+    pub on_time: u64,
+    pub off_time: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ExpectedSequence {
+    pub cycles: Vec<ExpectedCycle>,
+}
+
+/// Parses a sequence of horn cycles in the notation of `horn_proto::pattern`,
+/// e.g. `100/100,200/300` or `3x(100/100) 500/30`.
+pub fn parse_expected_sequence(sequence: &str) -> Result<ExpectedSequence, String> {
+    let sequence = pattern::parse_sequence(sequence).map_err(|e| e.to_string())?;
+    // The notation only allows non-negative times
+    let millis = |time: i32| u64::try_from(time).unwrap_or(0);
+    Ok(ExpectedSequence {
+        cycles: sequence
+            .horn_cycles
+            .iter()
+            .map(|cycle| ExpectedCycle {
+                on_time: millis(cycle.on_time),
+                off_time: millis(cycle.off_time),
+            })
+            .collect(),
+    })
+}
+
+/// Records the transitions of the target value and of the horn state with microsecond
+/// resolution. The transitions are written to the CSV and VCD files as they happen,
+/// so that the files are complete even if the software horn is killed.
+pub struct Timeline {
+    start: Instant,
+    csv: Option<BufWriter<File>>,
+    vcd: Option<BufWriter<File>>,
+    last_target: bool,
+    last_horn: bool,
+    // The points in time at which the horn was switched on and off
+    horn_transitions: Vec<(Duration, bool)>,
+}
+
+impl Timeline {
+    pub fn new(csv: Option<&Path>, vcd: Option<&Path>) -> std::io::Result<Self> {
+        let start = Instant::now();
+        let start_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let csv = csv
+            .map(|path| -> std::io::Result<_> {
+                let mut csv = BufWriter::new(File::create(path)?);
+                writeln!(csv, "elapsed_us,unix_time_us,signal,value")?;
+                csv.flush()?;
+                info!("Recording the horn timeline as CSV to {}", path.display());
+                Ok(csv)
+            })
+            .transpose()?;
+        let vcd = vcd
+            .map(|path| -> std::io::Result<_> {
+                let mut vcd = BufWriter::new(File::create(path)?);
+                write_vcd_header(&mut vcd, start_time)?;
+                vcd.flush()?;
+                info!("Recording the horn timeline as VCD to {}", path.display());
+                Ok(vcd)
+            })
+            .transpose()?;
+        Ok(Self {
+            start,
+            csv,
+            vcd,
+            // The initial values of both signals in the VCD file
+            last_target: false,
+            last_horn: false,
+            horn_transitions: Vec::new(),
+        })
+    }
+
+    /// Records the value of a signal at the given point in time, if it changed.
+    pub fn record(&mut self, signal: Signal, is_active: bool, at: Instant) {
+        let last = match signal {
+            Signal::Target => &mut self.last_target,
+            Signal::Horn => &mut self.last_horn,
+        };
+        if *last == is_active {
+            return;
+        }
+        *last = is_active;
+
+        let elapsed = at.saturating_duration_since(self.start);
+        if signal == Signal::Horn {
+            self.horn_transitions.push((elapsed, is_active));
+        }
+        let unix_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        if let Some(csv) = &mut self.csv {
+            if let Err(e) = writeln!(
+                csv,
+                "{},{},{},{is_active}",
+                elapsed.as_micros(),
+                unix_time.as_micros(),
+                signal.name()
+            )
+            .and_then(|_| csv.flush())
+            {
+                warn!("Failed to write the horn timeline as CSV: {e}");
+            }
+        }
+        if let Some(vcd) = &mut self.vcd {
+            if let Err(e) = writeln!(
+                vcd,
+                "#{}\n{}{}",
+                elapsed.as_micros(),
+                u8::from(is_active),
+                signal.vcd_identifier()
+            )
+            .and_then(|_| vcd.flush())
+            {
+                warn!("Failed to write the horn timeline as VCD: {e}");
+            }
+        }
+    }
+
+    /// The measured on and off times of the horn, starting with its first activation.
+    /// The off time of the last cycle is unknown until the horn is activated again.
+    fn measured_cycles(&self) -> Vec<(Duration, Option<Duration>)> {
+        let activations: Vec<_> = self
+            .horn_transitions
+            .iter()
+            .skip_while(|(_, is_active)| !is_active)
+            .collect();
+        activations
+            .chunks(2)
+            .enumerate()
+            .filter_map(|(index, transitions)| {
+                let (on_at, _) = transitions[0];
+                let (off_at, _) = transitions.get(1)?;
+                let next_on_at = activations.get(2 * index + 2).map(|(at, _)| *at);
+                Some((
+                    off_at.saturating_sub(*on_at),
+                    next_on_at.map(|next_on_at| next_on_at.saturating_sub(*off_at)),
+                ))
+            })
+            .collect()
+    }
+
+    /// The time at which the horn was switched on, if it is still on,
+    /// i.e. the last cycle was not completed.
+    fn unfinished_activation(&self) -> Option<Duration> {
+        match self.horn_transitions.last() {
+            Some((on_at, true)) => Some(*on_at),
+            _ => None,
+        }
+    }
+
+    /// Logs the measured horn cycles and compares them with the expected cycles, if given.
+    /// Returns whether all expected cycles were met within the tolerance and the last cycle finished.
+    pub fn log_summary(&self, expected: &[ExpectedCycle], tolerance: Duration) -> bool {
+        let measured = self.measured_cycles();
+        info!(
+            "Recorded {} horn transitions and {} complete horn cycles",
+            self.horn_transitions.len(),
+            measured.len()
+        );
+        let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let unfinished = self.unfinished_activation();
+        if let Some(on_at) = unfinished {
+            warn!(
+                "cycle {}: the horn was switched on at {:.1} ms and is still on, the cycle is unfinished",
+                measured.len() + 1,
+                millis(on_at)
+            );
+        }
+        if expected.is_empty() {
+            for (index, (on_time, off_time)) in measured.iter().enumerate() {
+                match off_time {
+                    Some(off_time) => info!(
+                        "cycle {}: on {:.1} ms, off {:.1} ms",
+                        index + 1,
+                        millis(*on_time),
+                        millis(*off_time)
+                    ),
+                    None => info!("cycle {}: on {:.1} ms", index + 1, millis(*on_time)),
+                }
+            }
+            return true;
+        }
+
+        let within = |measured: Duration, expected: u64| {
+            let expected = Duration::from_millis(expected);
+            measured.max(expected) - measured.min(expected) <= tolerance
+        };
+        let deviation = |measured: Duration, expected: u64| millis(measured) - expected as f64;
+        let mut passed = 0;
+        for (index, expected_cycle) in expected.iter().enumerate() {
+            let Some((on_time, off_time)) = measured.get(index) else {
+                warn!(
+                    "cycle {}: expected on {} ms, off {} ms, but not recorded",
+                    index + 1,
+                    expected_cycle.on_time,
+                    expected_cycle.off_time
+                );
+                continue;
+            };
+            // The off time of the last expected cycle is not measured
+            // if the horn is not activated afterwards
+            let off_time_ok = off_time
+                .map(|off_time| within(off_time, expected_cycle.off_time))
+                .unwrap_or(index + 1 == expected.len());
+            let ok = within(*on_time, expected_cycle.on_time) && off_time_ok;
+            let off_time = match off_time {
+                Some(off_time) => format!(
+                    "{:.1} ms ({:+.1} ms)",
+                    millis(*off_time),
+                    deviation(*off_time, expected_cycle.off_time)
+                ),
+                None => "not measured".to_string(),
+            };
+            let message = format!(
+                "cycle {}: on {:.1} ms ({:+.1} ms), expected {} ms; off {off_time}, expected {} ms",
+                index + 1,
+                millis(*on_time),
+                deviation(*on_time, expected_cycle.on_time),
+                expected_cycle.on_time,
+                expected_cycle.off_time
+            );
+            if ok {
+                passed += 1;
+                info!("{message}: OK");
+            } else {
+                warn!("{message}: DEVIATION");
+            }
+        }
+        if measured.len() > expected.len() {
+            warn!(
+                "{} more horn cycles recorded than expected",
+                measured.len() - expected.len()
+            );
+        }
+        info!(
+            "{passed} of {} expected horn cycles within a tolerance of {} ms",
+            expected.len(),
+            tolerance.as_millis()
+        );
+        passed == expected.len() && measured.len() == expected.len() && unfinished.is_none()
+    }
+}
+
+fn write_vcd_header(vcd: &mut impl Write, start_time: Duration) -> std::io::Result<()> {
+    writeln!(
+        vcd,
+        "$date {} s since the Unix epoch $end",
+        start_time.as_secs()
+    )?;
+    writeln!(vcd, "$version software-horn $end")?;
+    writeln!(vcd, "$timescale 1us $end")?;
+    writeln!(vcd, "$scope module horn $end")?;
+    for signal in [Signal::Target, Signal::Horn] {
+        writeln!(
+            vcd,
+            "$var wire 1 {} {} $end",
+            signal.vcd_identifier(),
+            signal.name()
+        )?;
+    }
+    writeln!(vcd, "$upscope $end")?;
+    writeln!(vcd, "$enddefinitions $end")?;
+    writeln!(vcd, "#0")?;
+    writeln!(vcd, "$dumpvars")?;
+    writeln!(vcd, "0t")?;
+    writeln!(vcd, "0h")?;
+    writeln!(vcd, "$end")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Records the horn transitions at the given milliseconds since the start
+    fn timeline(transitions: &[(u64, bool)]) -> Timeline {
+        let mut timeline = Timeline::new(None, None).unwrap();
+        let start = timeline.start;
+        for (at, is_active) in transitions {
+            timeline.record(Signal::Horn, *is_active, start + Duration::from_millis(*at));
+        }
+        timeline
+    }
+
+    fn cycles(sequence: &str) -> Vec<ExpectedCycle> {
+        parse_expected_sequence(sequence).unwrap().cycles
+    }
+
+    #[test]
+    fn expected_sequences_use_the_pattern_notation() {
+        let cycle = |on_time, off_time| ExpectedCycle { on_time, off_time };
+        assert_eq!(
+            cycles("100/100,200/300"),
+            [cycle(100, 100), cycle(200, 300)]
+        );
+        assert_eq!(
+            cycles("2x(60/40) 500/30"),
+            [cycle(60, 40), cycle(60, 40), cycle(500, 30)]
+        );
+        assert!(parse_expected_sequence("100-100").is_err());
+    }
+
+    #[test]
+    fn cycles_are_measured_from_the_first_activation() {
+        let timeline = timeline(&[
+            (50, true),
+            (150, true),
+            (250, false),
+            (400, true),
+            (500, false),
+        ]);
+        let millis = Duration::from_millis;
+        assert_eq!(
+            timeline.measured_cycles(),
+            [(millis(200), Some(millis(150))), (millis(100), None)]
+        );
+        assert_eq!(timeline.unfinished_activation(), None);
+    }
+
+    #[test]
+    fn matching_cycles_pass_within_the_tolerance() {
+        let timeline = timeline(&[(0, true), (105, false), (195, true), (400, false)]);
+        let tolerance = Duration::from_millis(10);
+        assert!(timeline.log_summary(&cycles("100/100,200/300"), tolerance));
+        assert!(!timeline.log_summary(&cycles("100/50,200/300"), tolerance));
+        assert!(!timeline.log_summary(&cycles("100/100"), tolerance));
+    }
+
+    #[test]
+    fn unfinished_cycle_fails_the_comparison() {
+        let timeline = timeline(&[(0, true), (100, false), (200, true)]);
+        assert_eq!(
+            timeline.unfinished_activation(),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(timeline.measured_cycles().len(), 1);
+        assert!(!timeline.log_summary(&cycles("100/100"), Duration::from_millis(10)));
+        assert!(timeline.log_summary(&[], Duration::from_millis(10)));
+    }
+}